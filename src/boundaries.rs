@@ -0,0 +1,287 @@
+// Plate boundaries
+//
+// Every tick each pair of neighboring cells on different plates is found and classified by how the two plates move
+// against each other where they meet. The difference of the plates' velocities is split into the part across the
+// boundary, which says whether they close in or pull apart, and the part along it. Whichever is larger decides
+// whether the boundary is convergent, divergent or transform.
+//
+// PlateBoundaries holds the result for the processes that work along boundaries, and the boundaries are drawn as
+// colored lines on the globe (red convergent, cyan divergent, green transform). The boundary key hides them.
+
+use bevy::{
+    prelude::*,
+    render::{render_asset::RenderAssetUsages, render_resource::PrimitiveTopology},
+};
+
+use crate::{columns::Columns, grid::Grid, plates::Plates, settings::Settings};
+
+// how far lines and rings drawn on the globe float above the ground so the globe doesn't hide them
+pub(crate) const OVERLAY_LIFT: f32 = 0.003;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BoundaryKind
+{
+    Convergent,
+    Divergent,
+    Transform,
+}
+
+// One side of the edge between two neighboring cells on different plates
+#[derive(Clone, Copy, Debug)]
+pub struct BoundaryEdge {
+    pub cell: u32,
+    pub neighbor: u32,
+
+    // plates of the cell and of the neighbor
+    pub plate: u32,
+    pub other_plate: u32,
+
+    pub kind: BoundaryKind,
+
+    // point on the globe where the two cells meet, and the direction from there across the boundary toward the
+    // neighbor, both unit vectors
+    pub midpoint: Vec3,
+    pub across: Vec3,
+
+    // speed the plates close in at across the boundary, negative where they pull apart, in globe radii per Myr
+    pub convergence: f32,
+
+    // speed the plates slide past each other along the boundary
+    pub shear: f32,
+}
+
+// Every boundary edge of the current tick, each one is stored from both sides so every boundary cell can list its own
+#[derive(Resource, Default)]
+pub struct PlateBoundaries {
+    // grouped by cell, cell c's edges are edges[edge_start[c]..edge_start[c + 1]]
+    edges: Vec<BoundaryEdge>,
+    edge_start: Vec<usize>,
+}
+
+// the queries for processes that act along boundaries
+impl PlateBoundaries {
+    pub fn edges(&self) -> &[BoundaryEdge] {
+        &self.edges
+    }
+
+    // edges between a cell and its neighbors on other plates, empty away from boundaries
+    pub fn at(&self, cell: usize) -> &[BoundaryEdge] {
+        match (self.edge_start.get(cell), self.edge_start.get(cell + 1)) {
+            (Some(&start), Some(&end)) => &self.edges[start..end],
+            _ => &[],
+        }
+    }
+
+    // what kind of boundary a cell lies on, decided by its edge with the fastest relative motion
+    pub fn kind(&self, cell: usize) -> Option<BoundaryKind> {
+        self.at(cell)
+            .iter()
+            .max_by(|a, b| a.convergence.hypot(a.shear).total_cmp(&b.convergence.hypot(b.shear)))
+            .map(|edge| edge.kind)
+    }
+
+    // cells lying on a kind of boundary, in order
+    pub fn cells(&self, kind: BoundaryKind) -> impl Iterator<Item = usize> + '_ {
+        (0..self.edge_start.len().saturating_sub(1)).filter(move |&cell| self.kind(cell) == Some(kind))
+    }
+
+    // finds and classifies every boundary edge of the plates as they are now
+    pub fn classify(&mut self, grid: &Grid, columns: &Columns, plates: &Plates) {
+        self.edges.clear();
+        self.edge_start.clear();
+        if !columns.fits(grid) {
+            return;
+        }
+
+        for cell in 0..grid.cell_count() {
+            self.edge_start.push(self.edges.len());
+            let plate_id = columns.plate_id[cell];
+            let Some(plate) = plates.plates.get(plate_id as usize) else {
+                continue;
+            };
+
+            let dir = grid.direction(cell);
+            for &neighbor in grid.neighbors(cell) {
+                let other_id = columns.plate_id[neighbor as usize];
+                if other_id == plate_id {
+                    continue;
+                }
+                let Some(other) = plates.plates.get(other_id as usize) else {
+                    continue;
+                };
+
+                //split the plate's motion relative to the other one at the point where the two cells meet
+                let neighbor_dir = grid.direction(neighbor as usize);
+                let midpoint = (dir + neighbor_dir).normalize();
+                let across = (neighbor_dir - dir).reject_from_normalized(midpoint).normalize_or_zero();
+                let relative = plate.velocity_at(midpoint) - other.velocity_at(midpoint);
+                let convergence = relative.dot(across);
+                let shear = (relative - across * convergence).length();
+
+                let kind = if convergence.abs() <= shear {
+                    BoundaryKind::Transform
+                } else if convergence > 0. {
+                    BoundaryKind::Convergent
+                } else {
+                    BoundaryKind::Divergent
+                };
+
+                self.edges.push(BoundaryEdge {
+                    cell: cell as u32,
+                    neighbor,
+                    plate: plate_id,
+                    other_plate: other_id,
+                    kind,
+                    midpoint,
+                    across,
+                    convergence,
+                    shear,
+                });
+            }
+        }
+        self.edge_start.push(self.edges.len());
+    }
+}
+
+// Marks the line mesh drawn over the globe, a child of the globe entity so it turns with it
+#[derive(Component)]
+pub struct BoundaryOverlay;
+
+// finds and classifies the boundary edges, run on the fixed timestep after the plates move and whenever the plates
+// are replaced (a new run, a load, a step back)
+pub fn classify_boundaries(
+    grid: Res<Grid>,
+    columns: Res<Columns>,
+    plates: Res<Plates>,
+    mut boundaries: ResMut<PlateBoundaries>,
+) {
+    boundaries.classify(&grid, &columns, &plates);
+}
+
+// empty line mesh for render_setup to hang under the globe, draw_boundaries fills it in
+pub fn empty_overlay_mesh() -> Mesh {
+    Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, Vec::<[f32; 3]>::new())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, Vec::<[f32; 4]>::new())
+}
+
+// redraws the boundary lines whenever the boundaries change
+// each edge is drawn once, as a line as long as the gap between the two cells lying across the middle of it
+pub fn draw_boundaries(
+    boundaries: Res<PlateBoundaries>,
+    columns: Res<Columns>,
+    grid: Res<Grid>,
+    overlay_query: Query<&Handle<Mesh>, With<BoundaryOverlay>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let mut positions = Vec::new();
+    let mut colors = Vec::new();
+    if columns.fits(&grid) {
+        for edge in boundaries.edges().iter().filter(|edge| edge.cell < edge.neighbor) {
+            let (cell, neighbor) = (edge.cell as usize, edge.neighbor as usize);
+            let (dir, neighbor_dir) = (grid.direction(cell), grid.direction(neighbor));
+            let midpoint = edge.midpoint;
+            let along = midpoint.cross(edge.across) * dir.angle_between(neighbor_dir) / 2.;
+            let radius = columns.height(cell).max(columns.height(neighbor)) + OVERLAY_LIFT;
+
+            let color = match edge.kind {
+                BoundaryKind::Convergent => Color::RED,
+                BoundaryKind::Divergent => Color::CYAN,
+                BoundaryKind::Transform => Color::LIME_GREEN,
+            };
+            for end in [midpoint + along, midpoint - along] {
+                positions.push((end.normalize() * radius).to_array());
+                colors.push(color.as_linear_rgba_f32());
+            }
+        }
+    }
+
+    for mesh in &overlay_query {
+        if let Some(mesh) = meshes.get_mut(mesh) {
+            mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions.clone());
+            mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors.clone());
+        }
+    }
+}
+
+// the boundary key shows or hides the lines
+pub fn boundary_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    mut overlay_query: Query<&mut Visibility, With<BoundaryOverlay>>,
+) {
+    if !keyboard_input.just_pressed(settings.keys.toggle_boundaries) {
+        return;
+    }
+    for mut visibility in &mut overlay_query {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Inherited,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{columns::RockType, config::WorldConfig, grid::GridKind, plates::Plate};
+
+    // the boundaries of two plates meeting along the meridian x = 0, each turning about its own pole
+    // the cube-sphere grid's edges near +z cross that meridian square on
+    fn boundaries_between(pole: Vec3, speed: f32, other_pole: Vec3, other_speed: f32) -> PlateBoundaries {
+        let config = WorldConfig { grid: GridKind::CubeSphere, cube_face_cells: 8, ..default() };
+        let grid = Grid::from_config(&config);
+        let mut columns = Columns::new(grid.cell_count(), RockType::Basalt, &config);
+        for cell in 0..grid.cell_count() {
+            columns.plate_id[cell] = if grid.direction(cell).x > 0. { 0 } else { 1 };
+        }
+        let plates = Plates {
+            plates: vec![
+                Plate { id: 0, pole, angular_velocity: speed, ..default() },
+                Plate { id: 1, pole: other_pole, angular_velocity: other_speed, ..default() },
+            ],
+        };
+
+        let mut boundaries = PlateBoundaries::default();
+        boundaries.classify(&grid, &columns, &plates);
+        boundaries
+    }
+
+    // the kinds of the edges near +z, where the plates below move straight at, away from or past each other
+    fn kinds_at_front(boundaries: &PlateBoundaries) -> Vec<BoundaryKind> {
+        let front: Vec<&BoundaryEdge> = boundaries.edges().iter().filter(|edge| edge.midpoint.z > 0.9).collect();
+        assert!(!front.is_empty(), "no boundary near +z");
+        for edge in &front {
+            assert_ne!(edge.plate, edge.other_plate);
+            let back = boundaries.at(edge.neighbor as usize).iter().find(|back| back.neighbor == edge.cell);
+            let (cell, neighbor) = (edge.cell, edge.neighbor);
+            assert!(back.is_some_and(|back| back.kind == edge.kind), "edge {} -> {} is one sided", cell, neighbor);
+        }
+        front.iter().map(|edge| edge.kind).collect()
+    }
+
+    #[test]
+    fn plates_are_classified_by_how_they_meet() {
+        //turning about y moves the ground at +z along x, turning about x moves it along y, the line the plates meet on
+        let cases = [
+            (Vec3::Y, -0.01, 0.01, BoundaryKind::Convergent),
+            (Vec3::Y, 0.01, -0.01, BoundaryKind::Divergent),
+            (Vec3::X, 0.01, -0.01, BoundaryKind::Transform),
+        ];
+        for (pole, speed, other_speed, expected) in cases {
+            let boundaries = boundaries_between(pole, speed, pole, other_speed);
+            let kinds = kinds_at_front(&boundaries);
+            assert!(kinds.iter().all(|&kind| kind == expected), "expected {:?}, got {:?}", expected, kinds);
+            assert!(boundaries.cells(expected).next().is_some());
+        }
+    }
+
+    #[test]
+    fn plates_moving_together_have_no_relative_motion() {
+        let boundaries = boundaries_between(Vec3::Y, 0.01, Vec3::Y, 0.01);
+        assert!(!boundaries.edges().is_empty());
+        for edge in boundaries.edges() {
+            assert!(edge.convergence.abs() < 1e-6 && edge.shear < 1e-6);
+        }
+    }
+}
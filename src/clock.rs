@@ -0,0 +1,143 @@
+// Simulation clock
+//
+// Every simulation system lives in SimulationSet on the FixedUpdate schedule, which only runs while the clock is
+// running (or a single step has been asked for). Speeding up the clock shortens the fixed timestep, so a tick
+// always covers the same amount of geologic time and a run plays out the same at any speed.
+
+use bevy::prelude::*;
+
+use crate::{config::WorldConfig, settings::Settings};
+
+// the speeds the clock can be switched between
+pub const TIME_SCALES: [f32; 9] = [0.25, 0.5, 1., 2., 4., 8., 16., 32., 64.];
+
+// glyphs shown on the pause button
+pub const PAUSE_GLYPH: &str = "||";
+pub const PLAY_GLYPH: &str = ">";
+
+#[derive(SystemSet, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct SimulationSet;
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct SimulationClock {
+    pub running: bool,
+
+    // geologic time since the start of the run, in million years
+    pub time_myr: f64,
+
+    pub ticks: u64,
+
+    // multiplier on how many ticks happen per second of real time, one of TIME_SCALES
+    pub time_scale: f32,
+
+    // set while paused to let exactly one more tick through
+    pub step_requested: bool,
+}
+
+impl Default for SimulationClock {
+    fn default() -> Self {
+        Self {
+            running: true,
+            time_myr: 0.,
+            ticks: 0,
+            time_scale: 1.,
+            step_requested: false,
+        }
+    }
+}
+
+impl SimulationClock {
+    pub fn toggle(&mut self) {
+        self.running = !self.running;
+    }
+
+    // moves to the next faster (steps > 0) or slower (steps < 0) entry of TIME_SCALES
+    pub fn change_speed(&mut self, steps: i32) {
+        let current = TIME_SCALES
+            .iter()
+            .position(|scale| *scale >= self.time_scale)
+            .unwrap_or(TIME_SCALES.len() - 1) as i32;
+        let next = (current + steps).clamp(0, TIME_SCALES.len() as i32 - 1);
+        self.time_scale = TIME_SCALES[next as usize];
+    }
+
+    pub fn request_step(&mut self) {
+        if !self.running {
+            self.step_requested = true;
+        }
+    }
+}
+
+// Marks the text inside the pause button so its glyph can follow the clock
+#[derive(Component)]
+pub struct PauseText;
+
+// Marks the text showing geologic time and speed
+#[derive(Component)]
+pub struct ClockText;
+
+// run condition for SimulationSet
+pub fn simulation_running(clock: Res<SimulationClock>) -> bool {
+    clock.running || clock.step_requested
+}
+
+// starts every new run at time zero
+pub fn clock_setup(mut clock: ResMut<SimulationClock>) {
+    *clock = SimulationClock::default();
+}
+
+// last system of every tick, moves geologic time forward
+pub fn advance_clock(
+    config: Res<WorldConfig>,
+    mut clock: ResMut<SimulationClock>,
+) {
+    clock.ticks += 1;
+    clock.time_myr += config.myr_per_tick as f64;
+    clock.step_requested = false;
+}
+
+// keeps the fixed timestep in line with the config and the chosen speed
+pub fn sync_fixed_timestep(
+    config: Res<WorldConfig>,
+    clock: Res<SimulationClock>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+) {
+    fixed_time.set_timestep_seconds(config.timestep / clock.time_scale as f64);
+}
+
+// pauses and resumes, steps forward one tick while paused and changes the speed
+// Space, Period, ] and [ unless they were rebound in the settings
+pub fn clock_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    mut clock: ResMut<SimulationClock>,
+) {
+    let keys = &settings.keys;
+    if keyboard_input.just_pressed(keys.pause) {
+        clock.toggle();
+    }
+    if keyboard_input.just_pressed(keys.step) {
+        clock.request_step();
+    }
+    if keyboard_input.just_pressed(keys.speed_up) {
+        clock.change_speed(1);
+    }
+    if keyboard_input.just_pressed(keys.slow_down) {
+        clock.change_speed(-1);
+    }
+}
+
+// swaps the pause button between the pause and play glyphs and refreshes the time readout
+pub fn update_clock_text(
+    clock: Res<SimulationClock>,
+    mut pause_query: Query<&mut Text, (With<PauseText>, Without<ClockText>)>,
+    mut clock_query: Query<&mut Text, (With<ClockText>, Without<PauseText>)>,
+) {
+    for mut text in &mut pause_query {
+        text.sections[0].value = if clock.running { PAUSE_GLYPH } else { PLAY_GLYPH }.to_string();
+    }
+    for mut text in &mut clock_query {
+        text.sections[0].value = format!("{:.0} Myr  x{}", clock.time_myr, clock.time_scale);
+    }
+}
@@ -0,0 +1,110 @@
+// Per-cell terrain columns
+//
+// Every cell of the grid holds a column: bedrock at the bottom, loose sediment on top of it, and water on top of
+// that, along with what the crust under it is made of, how old and thick it is, and which plate it rides on. The
+// columns are kept as one Vec per layer rather than one Vec of structs, systems that only touch a layer or two
+// (erosion moving sediment, the mesh reading heights) then walk tightly packed memory.
+//
+// Heights are measured from the center of the globe, a globe radius is 1. The height of the ground, bedrock plus
+// sediment, is what the globe mesh shows. Water sits above the ground and is not part of it.
+
+use bevy::prelude::*;
+
+use crate::{config::WorldConfig, grid::Grid};
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum RockType
+{
+    // oceanic crust
+    #[default]
+    Basalt,
+
+    // continental crust, too light to be pulled down into the mantle
+    Granite,
+}
+
+#[derive(Resource, Reflect, Clone, Default)]
+#[reflect(Resource)]
+pub struct Columns {
+    // height of the top of the bedrock
+    pub bedrock: Vec<f32>,
+
+    // thickness of the loose sediment lying on the bedrock
+    pub sediment: Vec<f32>,
+
+    // depth of the water on the ground, and the sediment it carries as a depth it would settle to
+    pub water: Vec<f32>,
+    pub suspended_sediment: Vec<f32>,
+
+    // million years since the crust formed
+    pub crust_age: Vec<f32>,
+
+    // thickness of the crust from its surface down to the mantle
+    pub crust_thickness: Vec<f32>,
+
+    pub rock_type: Vec<RockType>,
+
+    // index into Plates of the plate the crust belongs to
+    pub plate_id: Vec<u32>,
+}
+
+impl Columns {
+    // new crust of the given type everywhere, all on plate 0 with nothing on top of it
+    pub fn new(cell_count: usize, rock_type: RockType, config: &WorldConfig) -> Self {
+        let mut columns = Self {
+            bedrock: vec![0.; cell_count],
+            sediment: vec![0.; cell_count],
+            water: vec![0.; cell_count],
+            suspended_sediment: vec![0.; cell_count],
+            crust_age: vec![0.; cell_count],
+            crust_thickness: vec![0.; cell_count],
+            rock_type: vec![rock_type; cell_count],
+            plate_id: vec![0; cell_count],
+        };
+        for cell in 0..cell_count {
+            columns.new_crust(cell, rock_type, config);
+        }
+        columns
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.bedrock.len()
+    }
+
+    // whether every layer has one entry per cell of the grid
+    pub fn fits(&self, grid: &Grid) -> bool {
+        let cell_count = grid.cell_count();
+        [
+            self.bedrock.len(),
+            self.sediment.len(),
+            self.water.len(),
+            self.suspended_sediment.len(),
+            self.crust_age.len(),
+            self.crust_thickness.len(),
+            self.rock_type.len(),
+            self.plate_id.len(),
+        ]
+        .iter()
+        .all(|&len| len == cell_count)
+    }
+
+    // height of the ground, bedrock plus sediment
+    pub fn height(&self, cell: usize) -> f32 {
+        self.bedrock[cell] + self.sediment[cell]
+    }
+
+    // heights of the ground of every cell, what the globe mesh is built from
+    pub fn heights(&self) -> Vec<f32> {
+        self.bedrock.iter().zip(&self.sediment).map(|(bedrock, sediment)| bedrock + sediment).collect()
+    }
+
+    // replaces a cell's crust with freshly formed bare crust, the plate and any water on it stay
+    pub fn new_crust(&mut self, cell: usize, rock_type: RockType, config: &WorldConfig) {
+        let (height, thickness) = config.fresh_crust(rock_type);
+        self.bedrock[cell] = height;
+        self.sediment[cell] = 0.;
+        self.crust_age[cell] = 0.;
+        self.crust_thickness[cell] = thickness;
+        self.rock_type[cell] = rock_type;
+    }
+}
@@ -0,0 +1,160 @@
+// World configuration
+//
+// Everything needed to reproduce a run lives in WorldConfig. Every generator and simulation system takes its
+// randomness from WorldConfig::rng with its own stream number, so a saved config replays the same run exactly. New
+// runs get a fresh seed unless the settings file sets one, which replays a recorded run from the menu.
+
+use bevy::prelude::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::{
+    columns::RockType,
+    erosion::ErosionSettings,
+    flexure::FlexureSettings,
+    forces::PlateForceSettings,
+    grid::GridKind,
+    hotspots::HotspotSettings,
+    isostasy::IsostasySettings,
+    lifecycle::LifecycleSettings,
+    orogeny::OrogenySettings,
+    plates::PlateGenSettings,
+    spreading::SpreadingSettings,
+    terrain::TerrainSettings,
+    thermal::ThermalSettings,
+};
+
+// random number streams, one per system so extra draws in one system never shift the numbers another one sees
+pub const PLATE_STREAM: u64 = 1;
+pub const TERRAIN_STREAM: u64 = 2;
+pub const RIFT_STREAM: u64 = 3;
+pub const HOTSPOT_STREAM: u64 = 4;
+
+// fields missing from an older settings file keep their defaults
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource, Default)]
+pub struct WorldConfig {
+    pub seed: u64,
+
+    // which cells the globe is split into
+    pub grid: GridKind,
+
+    // size of the lat/long grid
+    pub rows: u32,
+    pub cols: u32,
+
+    // the icosahedral grid splits each of its 20 faces into 4^ico_subdivisions triangles
+    pub ico_subdivisions: u32,
+
+    // cells along each edge of a cube-sphere face
+    pub cube_face_cells: u32,
+
+    // seconds of real time between simulation ticks
+    pub timestep: f64,
+
+    // million years of geologic time a single tick represents
+    pub myr_per_tick: f32,
+
+    // heights of freshly formed crust, relative to a globe radius of 1
+    pub oceanic_crust_height: f32,
+    pub continental_crust_height: f32,
+
+    // thickness of freshly formed crust, about 7 km under the oceans and 35 km under the continents on Earth
+    // heights are exaggerated about 50 times (the 0.04 between ocean floor and continent stands for 5 km), and so are
+    // thicknesses
+    pub oceanic_crust_thickness: f32,
+    pub continental_crust_thickness: f32,
+
+    pub plates: PlateGenSettings,
+    pub terrain: TerrainSettings,
+    pub erosion: ErosionSettings,
+    pub thermal: ThermalSettings,
+    pub orogeny: OrogenySettings,
+    pub spreading: SpreadingSettings,
+    pub lifecycle: LifecycleSettings,
+    pub isostasy: IsostasySettings,
+    pub flexure: FlexureSettings,
+    pub hotspots: HotspotSettings,
+    pub forces: PlateForceSettings,
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            grid: GridKind::LatLong,
+            rows: 100,
+            cols: 100,
+            ico_subdivisions: 5,
+            cube_face_cells: 40,
+            timestep: 0.1,
+            myr_per_tick: 1.0,
+            oceanic_crust_height: 0.98,
+            continental_crust_height: 1.02,
+            oceanic_crust_thickness: 0.055,
+            continental_crust_thickness: 0.27,
+            plates: PlateGenSettings::default(),
+            terrain: TerrainSettings::default(),
+            erosion: ErosionSettings::default(),
+            thermal: ThermalSettings::default(),
+            orogeny: OrogenySettings::default(),
+            spreading: SpreadingSettings::default(),
+            lifecycle: LifecycleSettings::default(),
+            isostasy: IsostasySettings::default(),
+            flexure: FlexureSettings::default(),
+            hotspots: HotspotSettings::default(),
+            forces: PlateForceSettings::default(),
+        }
+    }
+}
+
+impl WorldConfig {
+    // the same world for a new run, started from the given seed to replay a recorded run, or from a seed taken from
+    // the clock when there is none
+    pub fn for_new_run(&self, seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_nanos() as u64)
+                .unwrap_or(0)
+        });
+
+        Self {
+            seed,
+            ..self.clone()
+        }
+    }
+
+    // the numbers that size the chosen grid, (rows, cols), (subdivisions, 0) or (face cells, 0)
+    // both the ico and cube grids are capped at somewhere around half a million cells
+    pub fn grid_resolution(&self) -> (u32, u32) {
+        match self.grid {
+            GridKind::LatLong => (self.rows.max(3), self.cols.max(3)),
+            GridKind::Icosahedral => (self.ico_subdivisions.min(8), 0),
+            GridKind::CubeSphere => (self.cube_face_cells.clamp(1, 300), 0),
+        }
+    }
+
+    // height and thickness of freshly formed crust of a rock type
+    pub fn fresh_crust(&self, rock_type: RockType) -> (f32, f32) {
+        match rock_type {
+            RockType::Basalt => (self.oceanic_crust_height, self.oceanic_crust_thickness),
+            RockType::Granite => (self.continental_crust_height, self.continental_crust_thickness),
+        }
+    }
+
+    // the random number generator for one system, always starts from the same state for the same seed and stream
+    pub fn rng(&self, stream: u64) -> ChaCha8Rng {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        rng.set_stream(stream);
+        rng
+    }
+
+    // the random number generator for one tick of a system that draws numbers every tick
+    // it depends only on the tick, so a run that was saved and loaded or stepped back draws the same numbers again
+    pub fn tick_rng(&self, stream: u64, tick: u64) -> ChaCha8Rng {
+        let mut rng = self.rng(stream);
+        rng.set_word_pos((tick as u128) << 32);
+        rng
+    }
+}
@@ -0,0 +1,242 @@
+// Cube-sphere grid
+//
+// The globe is a cube blown up onto the sphere. Each of the six faces is an n x n array of cells, stored one face
+// after another and row by row inside a face, so a face's cells are a plain 2D image and the six of them make a
+// cubemap. Cells are spaced by equal angle rather than equal distance across the face (the equiangular gnomonic
+// projection), which keeps the largest cell less than 40% bigger than the smallest.
+//
+// Cell edges run along great circles, so a cell's area is just its four corners' spherical quad. Cells on the edge
+// of a face find their neighbors on the next face by stepping one cell past the edge and looking up that direction.
+
+use std::collections::HashSet;
+
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology},
+};
+
+use crate::grid::{spherical_triangle_area, SphereGrid};
+
+// (normal, u axis, v axis) of each face
+const FACES: [(Vec3, Vec3, Vec3); 6] = [
+    (Vec3::X, Vec3::NEG_Z, Vec3::Y),
+    (Vec3::NEG_X, Vec3::Z, Vec3::Y),
+    (Vec3::Y, Vec3::X, Vec3::NEG_Z),
+    (Vec3::NEG_Y, Vec3::X, Vec3::Z),
+    (Vec3::Z, Vec3::X, Vec3::Y),
+    (Vec3::NEG_Z, Vec3::NEG_X, Vec3::Y),
+];
+
+pub struct CubeSphereGrid {
+    // cells along each edge of a face
+    n: usize,
+
+    directions: Vec<Vec3>,
+    neighbors: Vec<Vec<u32>>,
+    areas: Vec<f32>,
+
+    // three cell ids per triangle, wound counter clockwise seen from outside
+    triangles: Vec<u32>,
+}
+
+impl CubeSphereGrid {
+    pub fn new(face_cells: u32) -> Self {
+        let n = face_cells.max(1) as usize;
+        let mut grid = Self { n, directions: Vec::new(), neighbors: Vec::new(), areas: Vec::new(), triangles: Vec::new() };
+
+        for face in 0..6 {
+            for v in 0..n {
+                for u in 0..n {
+                    grid.directions.push(grid.face_direction(face, u as f32 + 0.5, v as f32 + 0.5));
+
+                    let corners = [(0., 0.), (1., 0.), (1., 1.), (0., 1.)]
+                        .map(|(du, dv)| grid.face_direction(face, u as f32 + du, v as f32 + dv));
+                    grid.areas.push(
+                        spherical_triangle_area(corners[0], corners[1], corners[2])
+                            + spherical_triangle_area(corners[0], corners[2], corners[3]),
+                    );
+                }
+            }
+        }
+
+        grid.neighbors = (0..grid.directions.len())
+            .map(|cell| {
+                let (face, u, v) = grid.face_uv(cell);
+                [(1, 0), (-1, 0), (0, 1), (0, -1)]
+                    .into_iter()
+                    .map(|(du, dv)| grid.step(face, u, v, du, dv) as u32)
+                    .collect()
+            })
+            .collect();
+
+        //inside each face, two triangles per square of four cells
+        for face in 0..6 {
+            for v in 0..n - 1 {
+                for u in 0..n - 1 {
+                    let corners = [(u, v), (u + 1, v), (u + 1, v + 1), (u, v + 1)].map(|(u, v)| grid.index(face, u, v));
+                    grid.push_quad(corners);
+                }
+            }
+        }
+
+        //across the seams, the cells along a face's edge are joined to the ones facing them on the next face
+        let mut corner_triangles = HashSet::new();
+        for face in 0..6 {
+            for (du, dv) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                let edge: Vec<(usize, usize)> = (0..n)
+                    .map(|k| match (du, dv) {
+                        (1, _) => (n - 1, k),
+                        (-1, _) => (0, k),
+                        (_, 1) => (k, n - 1),
+                        _ => (k, 0),
+                    })
+                    .collect();
+
+                let other_face = grid.face_uv(grid.step(face, edge[0].0, edge[0].1, du, dv)).0;
+                if other_face < face {
+                    continue;
+                }
+                for pair in edge.windows(2) {
+                    let (a, b) = (pair[0], pair[1]);
+                    grid.push_quad([
+                        grid.index(face, a.0, a.1),
+                        grid.index(face, b.0, b.1),
+                        grid.step(face, b.0, b.1, du, dv),
+                        grid.step(face, a.0, a.1, du, dv),
+                    ]);
+                }
+            }
+
+            //three faces meet at every cube corner, their corner cells make a triangle
+            for (u, v) in [(0, 0), (n - 1, 0), (0, n - 1), (n - 1, n - 1)] {
+                let du = if u == 0 { -1 } else { 1 };
+                let dv = if v == 0 { -1 } else { 1 };
+                let mut corner = [grid.index(face, u, v), grid.step(face, u, v, du, 0), grid.step(face, u, v, 0, dv)];
+                corner.sort_unstable();
+                if corner_triangles.insert(corner) {
+                    grid.push_triangle(corner);
+                }
+            }
+        }
+
+        grid
+    }
+
+    fn index(&self, face: usize, u: usize, v: usize) -> usize {
+        (face * self.n + v) * self.n + u
+    }
+
+    fn face_uv(&self, cell: usize) -> (usize, usize, usize) {
+        let n = self.n;
+        (cell / (n * n), cell % n, (cell / n) % n)
+    }
+
+    // direction of a point on a face, u and v counted in cells from the face's corner
+    fn face_direction(&self, face: usize, u: f32, v: f32) -> Vec3 {
+        let (normal, u_axis, v_axis) = FACES[face];
+        let angle = |t: f32| (t / self.n as f32 * 2. - 1.) * std::f32::consts::FRAC_PI_4;
+        (normal + u_axis * angle(u).tan() + v_axis * angle(v).tan()).normalize()
+    }
+
+    // the cell one step away from (u, v), on the next face over when the step leaves this one
+    fn step(&self, face: usize, u: usize, v: usize, du: isize, dv: isize) -> usize {
+        let (nu, nv) = (u as isize + du, v as isize + dv);
+        if (0..self.n as isize).contains(&nu) && (0..self.n as isize).contains(&nv) {
+            return self.index(face, nu as usize, nv as usize);
+        }
+        self.cell_from_direction(self.face_direction(face, nu as f32 + 0.5, nv as f32 + 0.5))
+    }
+
+    fn push_triangle(&mut self, corners: [usize; 3]) {
+        let [p0, p1, p2] = corners.map(|id| self.directions[id]);
+        let outward = (p1 - p0).cross(p2 - p0).dot(p0 + p1 + p2) > 0.;
+        let order = if outward { [0, 1, 2] } else { [0, 2, 1] };
+        self.triangles.extend(order.map(|k| corners[k] as u32));
+    }
+
+    // corners in order around the quad, either way round
+    fn push_quad(&mut self, corners: [usize; 4]) {
+        self.push_triangle([corners[0], corners[1], corners[2]]);
+        self.push_triangle([corners[0], corners[2], corners[3]]);
+    }
+}
+
+impl SphereGrid for CubeSphereGrid {
+    fn cell_count(&self) -> usize {
+        self.directions.len()
+    }
+
+    fn direction(&self, cell: usize) -> Vec3 {
+        self.directions[cell]
+    }
+
+    fn cell_area(&self, cell: usize) -> f32 {
+        self.areas[cell]
+    }
+
+    fn neighbors(&self, cell: usize) -> &[u32] {
+        &self.neighbors[cell]
+    }
+
+    fn cell_from_direction(&self, dir: Vec3) -> usize {
+        //the face is the one whose normal the direction is closest to
+        let face = (0..6)
+            .max_by(|&f, &g| FACES[f].0.dot(dir).total_cmp(&FACES[g].0.dot(dir)))
+            .unwrap_or(0);
+        let (normal, u_axis, v_axis) = FACES[face];
+
+        let along = dir.dot(normal);
+        let cell_of = |axis: Vec3| {
+            let angle = (dir.dot(axis) / along).atan();
+            let t = (angle / std::f32::consts::FRAC_PI_4 + 1.) / 2. * self.n as f32;
+            (t.floor().max(0.) as usize).min(self.n - 1)
+        };
+        self.index(face, cell_of(u_axis), cell_of(v_axis))
+    }
+
+    // the spacing of the cells in the middle of a face
+    fn cell_angle(&self) -> f32 {
+        std::f32::consts::FRAC_PI_2 / self.n as f32
+    }
+
+    fn mesh(&self, heights: &[f32]) -> Mesh {
+        let norms: Vec<[f32; 3]> = self.directions.iter().map(|dir| dir.to_array()).collect();
+
+        Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, self.positions(heights))
+            .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, norms)
+            .with_inserted_indices(Indices::U32(self.triangles.clone()))
+    }
+
+    fn positions(&self, heights: &[f32]) -> Vec<[f32; 3]> {
+        self.directions.iter().zip(heights).map(|(dir, height)| (*dir * *height).to_array()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_invariants() {
+        for n in [1, 2, 3, 8, 40] {
+            let grid = CubeSphereGrid::new(n);
+            assert_eq!(grid.cell_count(), 6 * n as usize * n as usize, "n {n}");
+
+            for cell in 0..grid.cell_count() {
+                let neighbors = grid.neighbors(cell);
+                assert_eq!(neighbors.len(), 4, "n {n} cell {cell}");
+                for &neighbor in neighbors {
+                    assert_ne!(neighbor as usize, cell, "n {n} cell {cell} neighbors itself");
+                    let back = grid.neighbors(neighbor as usize).contains(&(cell as u32));
+                    assert!(back, "n {n} cell {cell} -> {neighbor} is one way");
+                }
+                assert_eq!(grid.cell_from_direction(grid.direction(cell)), cell, "n {n} cell {cell}");
+            }
+
+            let area: f64 = (0..grid.cell_count()).map(|cell| grid.cell_area(cell) as f64).sum();
+            let sphere = 4. * std::f64::consts::PI;
+            assert!((area - sphere).abs() < 1e-4 * sphere, "n {n} areas add up to {area}");
+        }
+    }
+}
@@ -0,0 +1,273 @@
+// Hydraulic erosion
+//
+// A pipe model: rain falls on every land cell, water flows to lower neighbors through virtual pipes whose flux
+// builds up from tick to tick, and the moving water picks up, carries and drops sediment. On the lat/long grid a
+// cell's pipes are the north, east, south and west ones of the original sketch, and each pole has one pipe to
+// every cell of the ring next to it. The other grids have one pipe per neighbor.
+//
+// Everything is moved between cells as volume (depth times cell area), so rock and sediment are conserved exactly
+// even where cells differ in size. Water only comes in as rain and leaves by evaporating or running into the sea,
+// where the sediment it carries settles.
+//
+// The water, the sediment it carries and the loose sediment on the ground are layers of the Columns. Eroded bedrock
+// thins the crust along with it.
+
+use bevy::prelude::*;
+
+use crate::{columns::Columns, config::WorldConfig, grid::Grid};
+
+// Settings for hydraulic erosion, part of WorldConfig. Depths are relative to a globe radius of 1
+#[derive(Reflect, Clone, Debug)]
+pub struct ErosionSettings {
+    pub enabled: bool,
+
+    // depth of rain added to every land cell each tick
+    pub rain: f32,
+
+    // fraction of each cell's water that evaporates each tick
+    pub evaporation: f32,
+
+    // how fast the flux through a pipe builds up for a given drop in water surface
+    pub flow_rate: f32,
+
+    // sediment the water can carry for a given speed and slope
+    pub capacity: f32,
+
+    // fractions of the gap between the carried sediment and the capacity settled or picked up each tick
+    pub deposition_rate: f32,
+    pub dissolve_rate: f32,
+
+    // bedrock erodes this much slower than loose sediment
+    pub bedrock_rate: f32,
+
+    // ground below this height is sea
+    pub sea_level: f32,
+}
+
+impl Default for ErosionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            rain: 0.0002,
+            evaporation: 0.05,
+            flow_rate: 0.5,
+            capacity: 0.5,
+            deposition_rate: 0.3,
+            dissolve_rate: 0.3,
+            bedrock_rate: 0.1,
+            sea_level: 1.0,
+        }
+    }
+}
+
+// The pipes between cells
+// the flux only carries the flow's momentum from tick to tick, so it is not saved and starts from still water
+#[derive(Resource, Default)]
+pub struct ErosionState {
+    // volume leaving through each pipe last tick, cell c's pipes are flux[flux_start[c]..flux_start[c + 1]] in the
+    // order of grid.neighbors(c)
+    flux: Vec<f32>,
+    flux_start: Vec<usize>,
+}
+
+impl ErosionState {
+    // still water everywhere
+    pub fn reset(&mut self) {
+        self.flux.clear();
+        self.flux_start.clear();
+    }
+
+    // makes sure the pipes match the grid
+    fn fit(&mut self, grid: &Grid) {
+        let cell_count = grid.cell_count();
+        if self.flux_start.len() != cell_count + 1 {
+            self.flux_start = Vec::with_capacity(cell_count + 1);
+            let mut start = 0;
+            for cell in 0..cell_count {
+                self.flux_start.push(start);
+                start += grid.neighbors(cell).len();
+            }
+            self.flux_start.push(start);
+            self.flux = vec![0.; start];
+        }
+    }
+}
+
+// starts a new run, or a loaded one, with still water
+pub fn erosion_setup(mut state: ResMut<ErosionState>) {
+    state.reset();
+}
+
+// one tick of rain, flow, erosion, sediment transport and evaporation, run on the fixed timestep
+pub fn erode(
+    config: Res<WorldConfig>,
+    grid: Res<Grid>,
+    mut columns: ResMut<Columns>,
+    mut state: ResMut<ErosionState>,
+) {
+    let settings = &config.erosion;
+    let cell_count = grid.cell_count();
+    if !settings.enabled || !columns.fits(&grid) {
+        return;
+    }
+    state.fit(&grid);
+
+    let mut h = columns.heights();
+    let Columns { bedrock, sediment: bed, water, suspended_sediment: suspended, crust_thickness, .. } = &mut *columns;
+    let ErosionState { flux, flux_start } = &mut *state;
+    let areas: Vec<f32> = (0..cell_count).map(|cell| grid.cell_area(cell)).collect();
+
+    //precipitation
+    for (cell, depth) in water.iter_mut().enumerate() {
+        if h[cell] >= settings.sea_level {
+            *depth += settings.rain;
+        }
+    }
+
+    //water flow, the flux through each pipe grows with the drop in water surface along it
+    //a cell can't send out more water than it holds, so its pipes are scaled down together if they would
+    for cell in 0..cell_count {
+        let surface = h[cell] + water[cell];
+        let pipes = flux_start[cell]..flux_start[cell + 1];
+        for (pipe, &neighbor) in flux[pipes.clone()].iter_mut().zip(grid.neighbors(cell)) {
+            let drop = surface - (h[neighbor as usize] + water[neighbor as usize]);
+            *pipe = (*pipe + settings.flow_rate * drop * areas[cell]).max(0.);
+        }
+
+        let outflow: f32 = flux[pipes.clone()].iter().sum();
+        let available = water[cell] * areas[cell];
+        if outflow > available {
+            let scaler = if outflow > 0. { available / outflow } else { 0. };
+            for pipe in &mut flux[pipes] {
+                *pipe *= scaler;
+            }
+        }
+    }
+
+    //water level update and sediment transfer. A cell keeps whatever share of its water its pipes don't carry off,
+    //and each pipe carries the same share of the cell's suspended sediment as it does of the cell's water, so neither
+    //can run below zero and the sediment that leaves a cell is what its neighbors get
+    let mut water_in = vec![0f32; cell_count];
+    let mut water_out = vec![0f32; cell_count];
+    let mut sediment_in = vec![0f32; cell_count];
+    let mut kept = vec![1f32; cell_count];
+    for cell in 0..cell_count {
+        let pipes = &flux[flux_start[cell]..flux_start[cell + 1]];
+        let outflow: f32 = pipes.iter().sum();
+        let available = water[cell] * areas[cell];
+        if outflow <= 0. || available <= 0. {
+            continue;
+        }
+        let leaving = (outflow / available).min(1.);
+        kept[cell] = 1. - leaving;
+        water_out[cell] = outflow;
+
+        let sediment_per_volume = suspended[cell] * areas[cell] * leaving / outflow;
+        for (pipe, &neighbor) in pipes.iter().zip(grid.neighbors(cell)) {
+            water_in[neighbor as usize] += pipe;
+            sediment_in[neighbor as usize] += pipe * sediment_per_volume;
+        }
+    }
+
+    for cell in 0..cell_count {
+        water[cell] = water[cell] * kept[cell] + water_in[cell] / areas[cell];
+        suspended[cell] = suspended[cell] * kept[cell] + sediment_in[cell] / areas[cell];
+
+        //how much water runs through the cell, and how steeply the ground drops to its lowest neighbor
+        let speed = (water_in[cell] + water_out[cell]) / 2. / areas[cell];
+        let dir = grid.direction(cell);
+        let slope = grid
+            .neighbors(cell)
+            .iter()
+            .map(|&neighbor| (h[cell] - h[neighbor as usize]) / dir.angle_between(grid.direction(neighbor as usize)))
+            .fold(0f32, f32::max);
+
+        //erosion, deposition and dissolution
+        let capacity = settings.capacity * speed.sqrt() * slope;
+        if suspended[cell] > capacity {
+            let deposited = (suspended[cell] - capacity) * settings.deposition_rate;
+            suspended[cell] -= deposited;
+            bed[cell] += deposited;
+            h[cell] += deposited;
+        } else {
+            //loose sediment goes first, bedrock only erodes once there is too little of it
+            let wanted = (capacity - suspended[cell]) * settings.dissolve_rate;
+            let dissolved = wanted.min(bed[cell]);
+            bed[cell] -= dissolved;
+            let eroded = (wanted - dissolved) * settings.bedrock_rate;
+            bedrock[cell] -= eroded;
+            crust_thickness[cell] = (crust_thickness[cell] - eroded).max(0.);
+            h[cell] -= dissolved + eroded;
+            suspended[cell] += dissolved + eroded;
+        }
+
+        //water reaching the sea drains away and drops everything it carries
+        if h[cell] < settings.sea_level {
+            bed[cell] += suspended[cell];
+            h[cell] += suspended[cell];
+            suspended[cell] = 0.;
+            water[cell] = 0.;
+        }
+
+        water[cell] *= 1. - settings.evaporation;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{columns::RockType, grid::GridKind};
+
+    // bedrock, loose sediment and carried sediment of the whole globe, as a volume
+    fn solid_volume(grid: &Grid, columns: &Columns) -> f64 {
+        (0..grid.cell_count())
+            .map(|cell| {
+                let depth = columns.bedrock[cell] + columns.sediment[cell] + columns.suspended_sediment[cell];
+                depth as f64 * grid.cell_area(cell) as f64
+            })
+            .sum()
+    }
+
+    #[test]
+    fn erosion_conserves_rock_and_sediment() {
+        for kind in [GridKind::LatLong, GridKind::Icosahedral, GridKind::CubeSphere] {
+            let mut config =
+                WorldConfig { grid: kind, rows: 30, cols: 40, ico_subdivisions: 3, cube_face_cells: 12, ..default() };
+            config.erosion.sea_level = 0.;
+            config.erosion.rain = 0.002;
+            let grid = Grid::from_config(&config);
+
+            let mut columns = Columns::new(grid.cell_count(), RockType::Granite, &config);
+            let mut rng = ChaCha8Rng::seed_from_u64(1);
+            for cell in 0..grid.cell_count() {
+                columns.bedrock[cell] += rng.gen_range(0.0..0.05);
+                columns.sediment[cell] = rng.gen_range(0.0..0.002);
+            }
+            let before = solid_volume(&grid, &columns);
+            let heights = columns.heights();
+
+            let mut world = World::new();
+            world.insert_resource(config);
+            world.insert_resource(grid);
+            world.insert_resource(columns);
+            world.init_resource::<ErosionState>();
+            for _ in 0..50 {
+                world.run_system_once(erode);
+                let columns = world.resource::<Columns>();
+                assert!(columns.water.iter().chain(&columns.suspended_sediment).all(|&depth| depth >= 0.));
+            }
+
+            let (grid, columns) = (world.resource::<Grid>(), world.resource::<Columns>());
+            let after = solid_volume(grid, columns);
+            assert!(((after - before) / before).abs() < 1e-6, "{:?}: {} became {}", kind, before, after);
+
+            //and the ground did change, so there was something to conserve
+            let moved = heights.iter().zip(columns.heights()).map(|(a, b)| (a - b).abs()).fold(0f32, f32::max);
+            assert!(moved > 1e-4, "{:?}: nothing eroded", kind);
+        }
+    }
+}
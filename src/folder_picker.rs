@@ -0,0 +1,324 @@
+// Choosing the save folder
+//
+// The Choose Save Folder button opens a directory picker drawn with Bevy UI. It starts in the current save folder
+// and lists the folders inside the one being looked at. Clicking a folder goes into it, Up goes to the parent, and
+// Use This Folder checks that saves can be written there before it is stored in the settings.
+
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+
+use crate::{
+    loading::FileBrowser,
+    settings::{validate_save_folder, Settings},
+    toast::ShowToast,
+};
+
+// only this many folders are listed, the panel has no scrolling
+const MAX_LISTED: usize = 12;
+
+// Sent by the Choose Save Folder button
+#[derive(Event)]
+pub struct OpenFolderPicker;
+
+// Root node of the picker, remembers the folder being looked at
+#[derive(Component)]
+pub struct FolderPicker {
+    current: PathBuf,
+}
+
+#[derive(Component)]
+pub enum FolderAction {
+    Enter(PathBuf),
+    Up,
+    Choose,
+    Cancel,
+}
+
+// the visible folders inside a folder, sorted by name
+fn subfolders(folder: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut folders: Vec<PathBuf> = std::fs::read_dir(folder)
+        .map_err(|error| error.to_string())?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .filter(|path| !path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.')))
+        .collect();
+    folders.sort();
+    Ok(folders)
+}
+
+// a button with a single line of text
+fn spawn_button(parent: &mut ChildBuilder, label: &str, width: Val, action: FolderAction) {
+    parent.spawn
+    (
+        (
+            ButtonBundle
+            {
+                style: Style
+                {
+                    width,
+                    height: Val::Px(40.0),
+                    border: UiRect::all(Val::Px(3.0)),
+                    padding: UiRect::horizontal(Val::Px(8.0)),
+                    justify_content: JustifyContent::Start,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                border_color: BorderColor(Color::WHITE),
+                background_color: Color::rgb(0.15, 0.15, 0.15).into(),
+                ..default()
+            },
+            action,
+        )
+    )
+
+    .with_children
+    (
+        |parent|
+        {
+            parent.spawn
+            (
+                TextBundle::from_section
+                (
+                    label,
+                    TextStyle
+                    {
+                        font: default(),
+                        font_size: 22.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                )
+            );
+        }
+    );
+}
+
+// spawns the picker looking at a folder, the whole panel is rebuilt every time the user moves to another folder
+fn spawn_folder_picker(commands: &mut Commands, folder: PathBuf) {
+    let listing = subfolders(&folder);
+
+    commands.spawn
+    (
+        (
+            // Create the panel the folders are listed in
+            NodeBundle
+            {
+                style: Style
+                {
+                    // Place the panel over the menu
+                    position_type: PositionType::Absolute,
+
+                    // Set the gap between the panel and the top right of the window
+                    top: Val::Px(120.0),
+                    right: Val::Px(20.0),
+
+                    // Set the width of the panel in pixels
+                    width: Val::Px(520.0),
+
+                    // List everything in a column
+                    flex_direction: FlexDirection::Column,
+
+                    // Add a 6 pixel gap between entries
+                    row_gap: Val::Px(6.0),
+
+                    // Leave some room around the contents
+                    padding: UiRect::all(Val::Px(10.0)),
+
+                    ..default()
+                },
+
+                // Darken the area behind the panel so it is readable over the globe
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+
+                ..default()
+            },
+
+            FolderPicker { current: folder.clone() },
+        )
+    )
+
+    .with_children
+    (
+        |parent|
+        {
+            // Title of the panel
+            parent.spawn
+            (
+                TextBundle::from_section
+                (
+                    "Choose Save Folder",
+                    TextStyle
+                    {
+                        font: default(),
+                        font_size: 40.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                )
+            );
+
+            // The folder being looked at
+            parent.spawn
+            (
+                TextBundle::from_section
+                (
+                    folder.display().to_string(),
+                    TextStyle
+                    {
+                        font: default(),
+                        font_size: 18.0,
+                        color: Color::rgb(0.6, 0.6, 0.6),
+                    },
+                )
+            );
+
+            if folder.parent().is_some()
+            {
+                spawn_button(parent, "..", Val::Percent(100.0), FolderAction::Up);
+            }
+
+            match &listing
+            {
+                Ok(folders) =>
+                {
+                    for path in folders.iter().take(MAX_LISTED)
+                    {
+                        let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+                        spawn_button(parent, &name, Val::Percent(100.0), FolderAction::Enter(path.clone()));
+                    }
+
+                    if folders.len() > MAX_LISTED
+                    {
+                        parent.spawn
+                        (
+                            TextBundle::from_section
+                            (
+                                format!("and {} more", folders.len() - MAX_LISTED),
+                                TextStyle
+                                {
+                                    font: default(),
+                                    font_size: 18.0,
+                                    color: Color::rgb(0.6, 0.6, 0.6),
+                                },
+                            )
+                        );
+                    }
+                }
+
+                Err(error) =>
+                {
+                    parent.spawn
+                    (
+                        TextBundle::from_section
+                        (
+                            format!("Could not read the folder: {}", error),
+                            TextStyle
+                            {
+                                font: default(),
+                                font_size: 18.0,
+                                color: Color::rgb(1.0, 0.4, 0.4),
+                            },
+                        )
+                    );
+                }
+            }
+
+            // Use This Folder and Cancel side by side
+            parent.spawn
+            (
+                NodeBundle
+                {
+                    style: Style
+                    {
+                        column_gap: Val::Px(10.0),
+                        margin: UiRect::top(Val::Px(4.0)),
+                        ..default()
+                    },
+                    ..default()
+                }
+            )
+
+            .with_children
+            (
+                |parent|
+                {
+                    spawn_button(parent, "Use This Folder", Val::Px(200.0), FolderAction::Choose);
+                    spawn_button(parent, "Cancel", Val::Px(120.0), FolderAction::Cancel);
+                }
+            );
+        }
+    );
+}
+
+// opens the picker in the current save folder, or the closest folder above it that still exists
+#[allow(clippy::type_complexity)]
+pub fn open_folder_picker(
+    mut commands: Commands,
+    mut events: EventReader<OpenFolderPicker>,
+    panel_query: Query<Entity, Or<(With<FolderPicker>, With<FileBrowser>)>>,
+    settings: Res<Settings>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+
+    //the picker and the file browser sit in the same spot
+    for entity in &panel_query {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let start = settings
+        .save_folder
+        .ancestors()
+        .find(|folder| folder.is_dir())
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    spawn_folder_picker(&mut commands, start);
+}
+
+// handles the buttons of the folder picker
+#[allow(clippy::type_complexity)]
+pub fn folder_button_system(
+    mut commands: Commands,
+    mut interaction_query: Query<(&Interaction, &FolderAction, &mut BorderColor), (Changed<Interaction>, With<Button>)>,
+    picker_query: Query<(Entity, &FolderPicker)>,
+    mut settings: ResMut<Settings>,
+    mut toasts: EventWriter<ShowToast>,
+) {
+    let Ok((picker, FolderPicker { current })) = picker_query.get_single() else {
+        return;
+    };
+
+    for (interaction, action, mut border_color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                let next = match action {
+                    FolderAction::Enter(path) => Some(path.clone()),
+                    FolderAction::Up => current.parent().map(Path::to_path_buf),
+                    FolderAction::Choose => match validate_save_folder(current) {
+                        Ok(()) => {
+                            settings.save_folder = current.clone();
+                            toasts.send(ShowToast::info(format!("Saving to {}", current.display())));
+                            None
+                        }
+                        Err(error) => {
+                            toasts.send(ShowToast::error(format!("Can not save to {}", error)));
+                            continue;
+                        }
+                    },
+                    FolderAction::Cancel => None,
+                };
+
+                commands.entity(picker).despawn_recursive();
+                if let Some(folder) = next {
+                    spawn_folder_picker(&mut commands, folder);
+                }
+
+                //the buttons are gone, the rest of the interactions belong to the old panel
+                return;
+            }
+            Interaction::Hovered => border_color.0 = Color::LIME_GREEN,
+            Interaction::None => border_color.0 = Color::WHITE,
+        }
+    }
+}
@@ -0,0 +1,128 @@
+// Plate driving forces
+//
+// Plates are not pushed around by hand-set speeds, they move the way the forces on them add up. Old oceanic crust
+// is denser than the mantle under it, so where it sinks at a trench it drags the rest of its plate after it (slab
+// pull, by far the strongest force). At a mid-ocean ridge the raised young crust slides off the ridge and pushes the
+// plates apart (ridge push). The mantle under a plate resists its motion (basal drag), and more so under the deep
+// keels of continents.
+//
+// Plates have no inertia worth speaking of, so each one turns at whatever rate makes the drag balance the driving
+// forces. Every force is turned into a torque about the center of the globe, and the drag of a plate turning at a
+// rotation Ω is a torque M Ω, where M sums up how far each cell of the plate lies from the axis. Solving M Ω = τ
+// gives the plate's Euler pole and angular speed, which the plate eases into over the response time. A plate
+// losing its last trench slows down, one gaining a long trench speeds up, and continents drift, collide and
+// scatter on their own.
+
+use bevy::prelude::*;
+
+use crate::{
+    boundaries::{BoundaryKind, PlateBoundaries},
+    columns::{Columns, RockType},
+    config::WorldConfig,
+    grid::Grid,
+    plates::Plates,
+};
+
+// Settings for the plate driving forces, part of WorldConfig. Forces are per radian of boundary and drag is per
+// steradian of plate, only their ratios matter
+#[derive(Reflect, Clone, Debug)]
+pub struct PlateForceSettings {
+    pub enabled: bool,
+
+    pub slab_pull: f32,
+    pub ridge_push: f32,
+
+    // drag under oceanic crust, and how many times that continents feel
+    pub basal_drag: f32,
+    pub continental_drag: f32,
+
+    // million years it takes a plate to get about two thirds of the way to the speed the forces set
+    pub response_time: f32,
+
+    // fastest a plate may turn, in radians per million years, keeps small plates with long trenches in check
+    pub max_speed: f32,
+}
+
+impl Default for PlateForceSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            slab_pull: 0.004,
+            ridge_push: 0.001,
+            basal_drag: 1.,
+            continental_drag: 3.,
+            response_time: 5.,
+            max_speed: 0.05,
+        }
+    }
+}
+
+// sets every plate's rotation from the forces acting on it, run on the fixed timestep once the plates of the tick
+// are settled, ahead of the next tick's move
+pub fn drive_plates(
+    config: Res<WorldConfig>,
+    grid: Res<Grid>,
+    boundaries: Res<PlateBoundaries>,
+    columns: Res<Columns>,
+    mut plates: ResMut<Plates>,
+) {
+    let settings = &config.forces;
+    if !settings.enabled || !columns.fits(&grid) {
+        return;
+    }
+
+    let plate_count = plates.plates.len();
+    let mut torques = vec![Vec3::ZERO; plate_count];
+    let mut drags = vec![Mat3::ZERO; plate_count];
+
+    //a cell at r turning with the plate at Ω moves at Ω × r, and its drag -c (Ω × r) has a torque of
+    //-c (I - r rᵀ) Ω about the center
+    for cell in 0..grid.cell_count() {
+        let plate = columns.plate_id[cell] as usize;
+        if plate >= plate_count {
+            continue;
+        }
+        let dir = grid.direction(cell);
+        let keel = if columns.rock_type[cell] == RockType::Granite { settings.continental_drag } else { 1. };
+        let drag = settings.basal_drag * keel * grid.cell_area(cell);
+        drags[plate] += (Mat3::IDENTITY - Mat3::from_cols(dir * dir.x, dir * dir.y, dir * dir.z)) * drag;
+    }
+
+    let densities = &config.orogeny;
+    for edge in boundaries.edges() {
+        let (cell, neighbor) = (edge.cell as usize, edge.neighbor as usize);
+        let plate = edge.plate as usize;
+        if plate >= plate_count {
+            continue;
+        }
+        let length = grid.direction(cell).angle_between(grid.direction(neighbor));
+
+        //slabs pull toward the trench as hard as they are heavy for the mantle, ridges push away from the ridge
+        let force = match edge.kind {
+            BoundaryKind::Convergent if columns.rock_type[cell] == RockType::Basalt => {
+                let density = densities.density(columns.rock_type[cell], columns.crust_age[cell]);
+                let other_density = densities.density(columns.rock_type[neighbor], columns.crust_age[neighbor]);
+                edge.across * settings.slab_pull * densities.slab_pull(density, other_density)
+            }
+            BoundaryKind::Divergent => -edge.across * settings.ridge_push,
+            _ => continue,
+        };
+        torques[plate] += edge.midpoint.cross(force) * length;
+    }
+
+    let settle = if settings.response_time > 0. {
+        1. - (-config.myr_per_tick / settings.response_time).exp()
+    } else {
+        1.
+    };
+    for (plate, (torque, drag)) in plates.plates.iter_mut().zip(torques.into_iter().zip(drags)) {
+        //plates without cells have nothing to move, the drag grows with a plate's area so the test is against its size
+        let size = drag.x_axis.length() + drag.y_axis.length() + drag.z_axis.length();
+        if drag.determinant().abs() <= 1e-6 * size.powi(3) {
+            continue;
+        }
+        let target = (drag.inverse() * torque).clamp_length_max(settings.max_speed);
+        let rotation = plate.rotation();
+        plate.set_rotation(rotation + (target - rotation) * settle);
+    }
+}
@@ -0,0 +1,252 @@
+// Globe grids
+//
+// Per-cell data (heights, plate ids, crust) is kept in flat Vecs with one entry per cell. A SphereGrid knows where
+// each cell sits on the unit sphere, how big it is, which cells touch it, and how to build the globe mesh from the
+// cells. Simulation systems only talk to the Grid resource through SphereGrid, so they run the same on every grid.
+// Which grid a run uses is part of WorldConfig, so saves and settings carry it along with everything else.
+
+use std::ops::Deref;
+
+use bevy::prelude::*;
+
+use crate::{config::WorldConfig, create_globe_rect_mesh, cube::CubeSphereGrid, ico::IcoGrid, tris_from_rect_heights};
+
+// The cells the globe is split into, numbered 0 to cell_count - 1
+pub trait SphereGrid: Send + Sync {
+    fn cell_count(&self) -> usize;
+
+    // unit vector pointing at a cell
+    fn direction(&self, cell: usize) -> Vec3;
+
+    // the share of the unit sphere's surface that belongs to a cell, the areas of all cells add up to 4 pi
+    fn cell_area(&self, cell: usize) -> f32;
+
+    // cells sharing an edge of the mesh with a cell
+    fn neighbors(&self, cell: usize) -> &[u32];
+
+    // the cell closest to a direction
+    fn cell_from_direction(&self, dir: Vec3) -> usize;
+
+    // how far crust has to turn before it lands on another cell, in radians
+    fn cell_angle(&self) -> f32;
+
+    // globe mesh with one vertex per cell, pushed out from the center by its height
+    fn mesh(&self, heights: &[f32]) -> Mesh;
+
+    // vertex positions of the mesh for a new set of heights
+    fn positions(&self, heights: &[f32]) -> Vec<[f32; 3]>;
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum GridKind
+{
+    // rows of latitude with one cell at each pole, sized by WorldConfig rows and cols
+    #[default]
+    LatLong,
+
+    // subdivided icosahedron, sized by WorldConfig ico_subdivisions
+    Icosahedral,
+
+    // six square faces of cube_face_cells x cube_face_cells cells
+    CubeSphere,
+}
+
+// The lat/long grid. Cell 0 is the north pole, then every column of each row between the poles, and the last cell
+// is the south pole, the same order tris_from_rect_heights lays out its vertices in
+pub struct LatLongGrid {
+    pub rows: usize,
+    pub cols: usize,
+    neighbors: Vec<Vec<u32>>,
+}
+
+impl LatLongGrid {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        let rows = rows.max(3);
+        let cols = cols.max(3);
+        let mut grid = Self { rows, cols, neighbors: Vec::new() };
+
+        grid.neighbors = (0..grid.cell_count())
+            .map(|cell| {
+                let (i, j) = grid.row_col(cell);
+                rect_cell_neighbors(i, j, rows, cols).into_iter().map(|(ni, nj)| grid.index(ni, nj) as u32).collect()
+            })
+            .collect();
+        grid
+    }
+
+    // flat index of [row, column], both poles are a single cell
+    pub fn index(&self, i: usize, j: usize) -> usize {
+        if i == 0 {
+            0
+        } else if i == self.rows - 1 {
+            self.cell_count() - 1
+        } else {
+            1 + (i - 1) * self.cols + j
+        }
+    }
+
+    pub fn row_col(&self, cell: usize) -> (usize, usize) {
+        if cell == 0 {
+            (0, 0)
+        } else if cell == self.cell_count() - 1 {
+            (self.rows - 1, 0)
+        } else {
+            (1 + (cell - 1) / self.cols, (cell - 1) % self.cols)
+        }
+    }
+}
+
+impl SphereGrid for LatLongGrid {
+    fn cell_count(&self) -> usize {
+        (self.rows - 2) * self.cols + 2
+    }
+
+    fn direction(&self, cell: usize) -> Vec3 {
+        let (i, j) = self.row_col(cell);
+        rect_cell_direction(i, j, self.rows, self.cols)
+    }
+
+    // rows are evenly spaced in height, which makes every band between them the same area (Archimedes' hat box)
+    // the poles get the half band closest to them
+    fn cell_area(&self, cell: usize) -> f32 {
+        let band = 4. * std::f32::consts::PI / (self.rows - 1) as f32;
+        if cell == 0 || cell == self.cell_count() - 1 {
+            band / 2.
+        } else {
+            band / self.cols as f32
+        }
+    }
+
+    fn neighbors(&self, cell: usize) -> &[u32] {
+        &self.neighbors[cell]
+    }
+
+    fn cell_from_direction(&self, dir: Vec3) -> usize {
+        let (i, j) = rect_cell_from_direction(dir, self.rows, self.cols);
+        self.index(i, j)
+    }
+
+    // the spacing of the columns on the equator
+    fn cell_angle(&self) -> f32 {
+        2. * std::f32::consts::PI / self.cols as f32
+    }
+
+    fn mesh(&self, heights: &[f32]) -> Mesh {
+        create_globe_rect_mesh(self.cols as u32, self.rows as u32, heights)
+    }
+
+    fn positions(&self, heights: &[f32]) -> Vec<[f32; 3]> {
+        tris_from_rect_heights(heights, self.rows, self.cols)
+    }
+}
+
+#[derive(Resource)]
+pub struct Grid {
+    pub kind: GridKind,
+
+    // the config values the grid was built from, see WorldConfig::grid_resolution
+    pub resolution: (u32, u32),
+
+    cells: Box<dyn SphereGrid>,
+}
+
+// the grid's SphereGrid methods can be called straight on the resource
+impl Deref for Grid {
+    type Target = dyn SphereGrid;
+
+    fn deref(&self) -> &Self::Target {
+        self.cells.as_ref()
+    }
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Self::from_config(&WorldConfig::default())
+    }
+}
+
+impl Grid {
+    pub fn new(kind: GridKind, resolution: (u32, u32)) -> Self {
+        let cells: Box<dyn SphereGrid> = match kind {
+            GridKind::LatLong => Box::new(LatLongGrid::new(resolution.0 as usize, resolution.1 as usize)),
+            GridKind::Icosahedral => Box::new(IcoGrid::new(resolution.0)),
+            GridKind::CubeSphere => Box::new(CubeSphereGrid::new(resolution.0)),
+        };
+        Self { kind, resolution, cells }
+    }
+
+    pub fn from_config(config: &WorldConfig) -> Self {
+        Self::new(config.grid, config.grid_resolution())
+    }
+
+    pub fn matches(&self, config: &WorldConfig) -> bool {
+        self.kind == config.grid && self.resolution == config.grid_resolution()
+    }
+}
+
+// area of the triangle on the unit sphere between three unit vectors (Van Oosterom and Strackee)
+pub fn spherical_triangle_area(a: Vec3, b: Vec3, c: Vec3) -> f32 {
+    let numerator = a.dot(b.cross(c)).abs();
+    let denominator = 1. + a.dot(b) + b.dot(c) + c.dot(a);
+    2. * numerator.atan2(denominator)
+}
+
+// number of cells in a grid, without building it
+pub fn grid_cell_count(kind: GridKind, resolution: (u32, u32)) -> usize {
+    match kind {
+        GridKind::LatLong => (resolution.0.max(3) as usize - 2) * resolution.1.max(3) as usize + 2,
+        GridKind::Icosahedral => 10 * (1usize << (2 * resolution.0)) + 2,
+        GridKind::CubeSphere => 6 * (resolution.0.max(1) as usize).pow(2),
+    }
+}
+
+// rebuilds the grid when the config asks for a different one, runs before anything reads the grid on entering a state
+pub fn grid_setup(config: Res<WorldConfig>, mut grid: ResMut<Grid>) {
+    if !grid.matches(&config) {
+        *grid = Grid::from_config(&config);
+    }
+}
+
+//returns the unit vector pointing at the vertex in row i and column j of the lat/long grid built by tris_from_rect_heights
+pub fn rect_cell_direction(i: usize, j: usize, rows: usize, cols: usize) -> Vec3 {
+    let v_val: f32 = (i as f32)/(rows as f32 - 1.);
+    let h_angle: f32 = 2. * std::f32::consts::PI * (j as f32)/(cols as f32);
+    let ring_radius = (0.25 - (v_val-0.5) * (v_val-0.5)).sqrt() * 2.;
+    Vec3::new(h_angle.cos() * ring_radius, 1. - 2. * v_val, h_angle.sin() * ring_radius)
+}
+
+//inverse of rect_cell_direction, finds the [row, column] of the vertex closest to a direction
+//both poles are a single vertex, so they always come back as column 0
+pub fn rect_cell_from_direction(dir: Vec3, rows: usize, cols: usize) -> (usize, usize) {
+    let dir = dir.normalize();
+    let v_val = (1. - dir.y) / 2.;
+    let i = ((v_val * (rows as f32 - 1.)).round() as usize).min(rows - 1);
+    if i == 0 || i == rows - 1 {
+        return (i, 0);
+    }
+
+    let mut h_angle = dir.z.atan2(dir.x);
+    if h_angle < 0. {
+        h_angle += 2. * std::f32::consts::PI;
+    }
+    let j = ((h_angle / (2. * std::f32::consts::PI) * cols as f32).round() as usize) % cols;
+    (i, j)
+}
+
+//finds the vertices sharing an edge with [i, j] on the lat/long grid
+//columns wrap around, and each pole is connected to every vertex of the ring next to it
+pub fn rect_cell_neighbors(i: usize, j: usize, rows: usize, cols: usize) -> Vec<(usize, usize)> {
+    if i == 0 {
+        return (0..cols).map(|col| (1, col)).collect();
+    }
+    if i == rows - 1 {
+        return (0..cols).map(|col| (rows - 2, col)).collect();
+    }
+
+    let mut neighbors = Vec::with_capacity(4);
+    neighbors.push(if i == 1 { (0, 0) } else { (i - 1, j) });
+    neighbors.push(if i == rows - 2 { (rows - 1, 0) } else { (i + 1, j) });
+    neighbors.push((i, (j + 1) % cols));
+    neighbors.push((i, (j + cols - 1) % cols));
+    neighbors
+}
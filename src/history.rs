@@ -0,0 +1,327 @@
+// Step back history
+//
+// Every few ticks the simulation state is written into a checkpoint. Only the oldest checkpoint keeps full copies
+// of the per-cell layers, every later one stores just the cells that changed since the checkpoint before it, unless
+// most of a layer changed (the crust ages everywhere every tick). When the history grows past its memory budget the
+// oldest checkpoint is folded into the next one and dropped.
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::{
+    clock::SimulationClock,
+    columns::{Columns, RockType},
+    erosion::ErosionState,
+    flexure::Flexure,
+    lifecycle::PlateEvents,
+    plates::{Plate, Plates},
+    settings::Settings,
+};
+
+// Sent by the step back button and hotkey
+#[derive(Event)]
+pub struct StepBack;
+
+// One per-cell layer inside a checkpoint
+enum Layer<T> {
+    Full(Vec<T>),
+
+    // (flat index, new value) for every cell that differs from the previous checkpoint
+    Delta(Vec<(u32, T)>),
+}
+
+impl<T: Copy + PartialEq> Layer<T> {
+    fn delta(previous: &[T], current: &[T]) -> Self {
+        Layer::Delta(
+            current
+                .iter()
+                .enumerate()
+                .filter(|(index, value)| previous.get(*index) != Some(*value))
+                .map(|(index, value)| (index as u32, *value))
+                .collect(),
+        )
+    }
+
+    // writes this layer over the values of the checkpoint before it
+    fn apply(&self, values: &mut Vec<T>) {
+        match self {
+            Layer::Full(full) => values.clone_from(full),
+            Layer::Delta(changes) => {
+                for (index, value) in changes {
+                    values[*index as usize] = *value;
+                }
+            }
+        }
+    }
+
+    fn size_in_bytes(&self) -> usize {
+        match self {
+            Layer::Full(full) => full.len() * std::mem::size_of::<T>(),
+            Layer::Delta(changes) => changes.len() * std::mem::size_of::<(u32, T)>(),
+        }
+    }
+
+    // a full layer for the first checkpoint, a delta against the newest one after that unless it would be bigger
+    fn next(latest: Option<&Vec<T>>, current: &[T]) -> Self {
+        let full = Layer::Full(current.to_vec());
+        match latest {
+            Some(latest) => {
+                let delta = Layer::delta(latest, current);
+                if delta.size_in_bytes() < full.size_in_bytes() { delta } else { full }
+            }
+            None => full,
+        }
+    }
+
+    // folds the layer of the checkpoint before this one in, leaving a full layer
+    fn fold(&mut self, oldest: &Layer<T>) {
+        let mut values = Vec::new();
+        oldest.apply(&mut values);
+        self.apply(&mut values);
+        *self = Layer::Full(values);
+    }
+}
+
+struct Checkpoint {
+    clock: SimulationClock,
+    plates: Vec<Plate>,
+
+    bedrock: Layer<f32>,
+    sediment: Layer<f32>,
+    water: Layer<f32>,
+    suspended_sediment: Layer<f32>,
+    crust_age: Layer<f32>,
+    crust_thickness: Layer<f32>,
+    rock_type: Layer<RockType>,
+    plate_id: Layer<u32>,
+}
+
+impl Checkpoint {
+    fn size_in_bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.plates.len() * std::mem::size_of::<Plate>()
+            + self.bedrock.size_in_bytes()
+            + self.sediment.size_in_bytes()
+            + self.water.size_in_bytes()
+            + self.suspended_sediment.size_in_bytes()
+            + self.crust_age.size_in_bytes()
+            + self.crust_thickness.size_in_bytes()
+            + self.rock_type.size_in_bytes()
+            + self.plate_id.size_in_bytes()
+    }
+}
+
+// memory held by a full copy of the columns
+fn columns_size_in_bytes(columns: &Columns) -> usize {
+    let cell_count = columns.cell_count();
+    cell_count * (6 * std::mem::size_of::<f32>() + std::mem::size_of::<RockType>() + std::mem::size_of::<u32>())
+}
+
+// set from the Settings when a run starts, see history_setup
+#[derive(Resource)]
+pub struct SimulationHistory {
+    // ticks between checkpoints
+    pub interval: u64,
+
+    // the most memory the checkpoints and the full copy of the newest one may use before the oldest checkpoints are
+    // dropped
+    pub memory_budget: usize,
+
+    checkpoints: VecDeque<Checkpoint>,
+
+    // full columns of the newest checkpoint, so a new delta can be made without replaying the whole history
+    latest: Option<Columns>,
+
+    // memory held by the checkpoints and by latest
+    used_bytes: usize,
+}
+
+impl Default for SimulationHistory {
+    fn default() -> Self {
+        Self {
+            interval: 10,
+            memory_budget: 64 * 1024 * 1024,
+            checkpoints: VecDeque::new(),
+            latest: None,
+            used_bytes: 0,
+        }
+    }
+}
+
+impl SimulationHistory {
+    pub fn clear(&mut self) {
+        self.checkpoints.clear();
+        self.latest = None;
+        self.used_bytes = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    pub fn record(&mut self, clock: &SimulationClock, plates: &Plates, columns: &Columns) {
+        //the grid might have been rebuilt at another size, a delta against the old grid would be meaningless
+        let same_shape = self.latest.as_ref().is_some_and(|latest| latest.cell_count() == columns.cell_count());
+        if !same_shape {
+            self.clear();
+        }
+
+        let latest = self.latest.as_ref();
+        let checkpoint = Checkpoint {
+            clock: clock.clone(),
+            plates: plates.plates.clone(),
+            bedrock: Layer::next(latest.map(|latest| &latest.bedrock), &columns.bedrock),
+            sediment: Layer::next(latest.map(|latest| &latest.sediment), &columns.sediment),
+            water: Layer::next(latest.map(|latest| &latest.water), &columns.water),
+            suspended_sediment: Layer::next(latest.map(|latest| &latest.suspended_sediment), &columns.suspended_sediment),
+            crust_age: Layer::next(latest.map(|latest| &latest.crust_age), &columns.crust_age),
+            crust_thickness: Layer::next(latest.map(|latest| &latest.crust_thickness), &columns.crust_thickness),
+            rock_type: Layer::next(latest.map(|latest| &latest.rock_type), &columns.rock_type),
+            plate_id: Layer::next(latest.map(|latest| &latest.plate_id), &columns.plate_id),
+        };
+
+        self.used_bytes += checkpoint.size_in_bytes();
+        self.checkpoints.push_back(checkpoint);
+        self.set_latest(Some(columns.clone()));
+
+        while self.used_bytes > self.memory_budget && self.checkpoints.len() > 1 {
+            self.drop_oldest();
+        }
+    }
+
+    // folds the oldest checkpoint into the one after it, which becomes the new full checkpoint
+    fn drop_oldest(&mut self) {
+        let Some(oldest) = self.checkpoints.pop_front() else {
+            return;
+        };
+        self.used_bytes -= oldest.size_in_bytes();
+
+        let Some(next) = self.checkpoints.front_mut() else {
+            return;
+        };
+        self.used_bytes -= next.size_in_bytes();
+
+        next.bedrock.fold(&oldest.bedrock);
+        next.sediment.fold(&oldest.sediment);
+        next.water.fold(&oldest.water);
+        next.suspended_sediment.fold(&oldest.suspended_sediment);
+        next.crust_age.fold(&oldest.crust_age);
+        next.crust_thickness.fold(&oldest.crust_thickness);
+        next.rock_type.fold(&oldest.rock_type);
+        next.plate_id.fold(&oldest.plate_id);
+
+        self.used_bytes += next.size_in_bytes();
+    }
+
+    // removes the newest checkpoint
+    fn drop_newest(&mut self) {
+        if let Some(newest) = self.checkpoints.pop_back() {
+            self.used_bytes -= newest.size_in_bytes();
+        }
+        self.set_latest(self.replay(self.checkpoints.len()));
+    }
+
+    // swaps in the full columns of the newest checkpoint, keeping their memory counted
+    fn set_latest(&mut self, latest: Option<Columns>) {
+        let size = |latest: &Option<Columns>| latest.as_ref().map_or(0, columns_size_in_bytes);
+        self.used_bytes = self.used_bytes - size(&self.latest) + size(&latest);
+        self.latest = latest;
+    }
+
+    // rebuilds the full columns of the first `count` checkpoints
+    fn replay(&self, count: usize) -> Option<Columns> {
+        if count == 0 {
+            return None;
+        }
+        let mut columns = Columns::default();
+        for checkpoint in self.checkpoints.iter().take(count) {
+            checkpoint.bedrock.apply(&mut columns.bedrock);
+            checkpoint.sediment.apply(&mut columns.sediment);
+            checkpoint.water.apply(&mut columns.water);
+            checkpoint.suspended_sediment.apply(&mut columns.suspended_sediment);
+            checkpoint.crust_age.apply(&mut columns.crust_age);
+            checkpoint.crust_thickness.apply(&mut columns.crust_thickness);
+            checkpoint.rock_type.apply(&mut columns.rock_type);
+            checkpoint.plate_id.apply(&mut columns.plate_id);
+        }
+        Some(columns)
+    }
+}
+
+// clears the history, takes the interval and memory budget from the settings and records the state a run starts from
+pub fn history_setup(
+    mut history: ResMut<SimulationHistory>,
+    settings: Res<Settings>,
+    clock: Res<SimulationClock>,
+    plates: Res<Plates>,
+    columns: Res<Columns>,
+) {
+    history.clear();
+    history.interval = settings.history_interval;
+    history.memory_budget = settings.history_memory_mb * 1024 * 1024;
+    history.record(&clock, &plates, &columns);
+}
+
+// runs after the clock has ticked, records a checkpoint every `interval` ticks
+pub fn record_checkpoint(
+    mut history: ResMut<SimulationHistory>,
+    clock: Res<SimulationClock>,
+    plates: Res<Plates>,
+    columns: Res<Columns>,
+) {
+    if history.interval > 0 && clock.ticks.is_multiple_of(history.interval) {
+        history.record(&clock, &plates, &columns);
+    }
+}
+
+// Comma (or its rebinding) steps back, the same as the "<||" button
+pub fn history_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    mut step_back: EventWriter<StepBack>,
+) {
+    if keyboard_input.just_pressed(settings.keys.step_back) {
+        step_back.send(StepBack);
+    }
+}
+
+// restores the newest checkpoint older than the current state and pauses so it can be looked at
+// the columns change, so refresh_globe_mesh rebuilds the globe from them
+// checkpoints don't keep the water flowing or the bent plate, those start over from still water and the local
+// deflections the way a loaded run does
+#[allow(clippy::too_many_arguments)]
+pub fn step_back(
+    mut events: EventReader<StepBack>,
+    mut history: ResMut<SimulationHistory>,
+    mut clock: ResMut<SimulationClock>,
+    mut plates: ResMut<Plates>,
+    mut columns: ResMut<Columns>,
+    mut plate_events: ResMut<PlateEvents>,
+    mut erosion: ResMut<ErosionState>,
+    mut flexure: ResMut<Flexure>,
+) {
+    for _ in events.read() {
+        //already sitting on the newest checkpoint, go to the one before it
+        let at_newest = history.checkpoints.back().is_some_and(|newest| newest.clock.ticks >= clock.ticks);
+        if at_newest && history.len() > 1 {
+            history.drop_newest();
+        }
+
+        let (Some(checkpoint), Some(state)) = (history.checkpoints.back(), history.latest.as_ref()) else {
+            continue;
+        };
+
+        //keep the speed the user picked rather than the one the checkpoint was recorded at
+        let time_scale = clock.time_scale;
+        *clock = checkpoint.clock.clone();
+        clock.time_scale = time_scale;
+        clock.running = false;
+        clock.step_requested = false;
+        plates.plates = checkpoint.plates.clone();
+        columns.clone_from(state);
+        plate_events.rewind(&clock);
+        erosion.reset();
+        flexure.deflection.clear();
+    }
+}
@@ -0,0 +1,325 @@
+// Mantle hotspots
+//
+// A hotspot is a plume of hot mantle rising from deep below the plates. It stays put while the plates drift over
+// it, and it keeps melting through whatever crust passes above, piling volcanoes onto it. Its heat also lifts the
+// plate into a broad swell, which is modelled by making the crust young again so it cools and sinks once more after
+// it has passed. Each volcano is carried off by its plate, goes extinct and erodes and sinks as its crust ages, so a
+// hotspot leaves a chain of islands and seamounts behind that gets older the further it is from the hotspot (Hawaii
+// and the Emperor seamounts).
+//
+// Hotspots are entities with a Hotspot component. Their positions are directions in the globe's own frame, which
+// is the mantle's: the grid never moves, only the crust is carried across it. A new run scatters a few hotspots from
+// the seed, more rise at random as the run goes on, and clicking the globe starts one under the cursor. They live out
+// their lifetime and then go quiet. Active hotspots are drawn as rings on the globe.
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use rand::Rng;
+
+use crate::{
+    boundaries::OVERLAY_LIFT,
+    clock::SimulationClock,
+    columns::Columns,
+    config::{WorldConfig, HOTSPOT_STREAM},
+    grid::Grid,
+    plates::random_unit_vector,
+    Shape,
+};
+
+// Settings for hotspots, part of WorldConfig. Random hotspots get between half and one and a half times the
+// strength, radius and lifetime set here, hotspots placed by hand get exactly these
+#[derive(Reflect, Clone, Debug)]
+pub struct HotspotSettings {
+    pub enabled: bool,
+
+    // hotspots a new run starts with, and the chance of a new one rising every million years
+    pub count: u32,
+    pub spawn_rate: f32,
+
+    // crust added at the middle of a hotspot every million years, fading out to nothing at its radius in radians
+    pub strength: f32,
+    pub radius: f32,
+
+    // million years a hotspot stays active
+    pub lifetime: f32,
+
+    // highest the volcanoes get above sea level
+    pub max_height: f32,
+
+    // crust over a hotspot, out to twice its radius, is reheated to this age in million years
+    pub swell_age: f32,
+}
+
+impl Default for HotspotSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            count: 4,
+            spawn_rate: 0.02,
+            strength: 0.1,
+            radius: 0.04,
+            lifetime: 150.,
+            max_height: 0.03,
+            swell_age: 20.,
+        }
+    }
+}
+
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component)]
+pub struct Hotspot {
+    // unit vector to the middle of the hotspot, fixed in the mantle
+    pub position: Vec3,
+
+    pub strength: f32,
+    pub radius: f32,
+    pub lifetime: f32,
+
+    // clock when the hotspot rose, hotspots rising during a tick rise at its end
+    pub born_myr: f64,
+    pub born_tick: u64,
+}
+
+impl Hotspot {
+    fn random(settings: &HotspotSettings, born_myr: f64, born_tick: u64, rng: &mut impl Rng) -> Self {
+        Self {
+            position: random_unit_vector(rng),
+            strength: settings.strength * rng.gen_range(0.5..1.5),
+            radius: settings.radius * rng.gen_range(0.5..1.5),
+            lifetime: settings.lifetime * rng.gen_range(0.5..1.5),
+            born_myr,
+            born_tick,
+        }
+    }
+
+    pub fn is_active(&self, clock: &SimulationClock) -> bool {
+        clock.ticks >= self.born_tick && clock.time_myr < self.born_myr + self.lifetime as f64
+    }
+}
+
+// replaces the hotspots of the last run with the ones a new run starts with
+pub fn hotspots_setup(
+    mut commands: Commands,
+    config: Res<WorldConfig>,
+    clock: Res<SimulationClock>,
+    hotspots: Query<Entity, With<Hotspot>>,
+) {
+    for entity in &hotspots {
+        commands.entity(entity).despawn();
+    }
+    if !config.hotspots.enabled {
+        return;
+    }
+
+    let mut rng = config.rng(HOTSPOT_STREAM);
+    for _ in 0..config.hotspots.count {
+        commands.spawn(Hotspot::random(&config.hotspots, clock.time_myr, clock.ticks, &mut rng));
+    }
+}
+
+// raises new hotspots and builds volcanoes over the active ones, run on the fixed timestep after the plates move
+pub fn hotspot_volcanism(
+    mut commands: Commands,
+    config: Res<WorldConfig>,
+    clock: Res<SimulationClock>,
+    grid: Res<Grid>,
+    mut columns: ResMut<Columns>,
+    hotspots: Query<&Hotspot>,
+) {
+    let settings = &config.hotspots;
+    if !settings.enabled || !columns.fits(&grid) {
+        return;
+    }
+
+    for hotspot in hotspots.iter().filter(|hotspot| hotspot.is_active(&clock)) {
+        //a hotspot narrower than the cells still reaches the cells next to it
+        let radius = hotspot.radius.max(grid.cell_angle());
+        let reach = (2. * radius).cos();
+        for cell in 0..grid.cell_count() {
+            let dir = grid.direction(cell);
+            if dir.dot(hotspot.position) < reach {
+                continue;
+            }
+            columns.crust_age[cell] = columns.crust_age[cell].min(settings.swell_age);
+
+            //a cone of new rock on top of the crust, growing slower as it nears its highest
+            let falloff = 1. - dir.angle_between(hotspot.position) / radius;
+            if falloff <= 0. {
+                continue;
+            }
+            let headroom = (1. - (columns.height(cell) - config.erosion.sea_level) / settings.max_height).clamp(0., 1.);
+            let added = hotspot.strength * config.myr_per_tick * falloff * headroom;
+            columns.bedrock[cell] += added;
+            columns.crust_thickness[cell] += added;
+        }
+    }
+
+    let mut rng = config.tick_rng(HOTSPOT_STREAM, clock.ticks);
+    let chance = settings.spawn_rate * config.myr_per_tick;
+    if chance > 0. && rng.gen_bool(chance.min(1.) as f64) {
+        let born_myr = clock.time_myr + config.myr_per_tick as f64;
+        commands.spawn(Hotspot::random(settings, born_myr, clock.ticks + 1, &mut rng));
+    }
+}
+
+// starts a hotspot where the globe is clicked, unless the click was on a button
+#[allow(clippy::too_many_arguments)]
+pub fn place_hotspot(
+    mut commands: Commands,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    config: Res<WorldConfig>,
+    clock: Res<SimulationClock>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    globe_query: Query<&GlobalTransform, With<Shape>>,
+    interactions: Query<&Interaction>,
+) {
+    let on_button = interactions.iter().any(|interaction| *interaction != Interaction::None);
+    if !mouse_input.just_pressed(MouseButton::Left) || on_button {
+        return;
+    }
+    let Some(cursor) = window_query.get_single().ok().and_then(|window| window.cursor_position()) else {
+        return;
+    };
+    let (Ok((camera, camera_transform)), Ok(globe_transform)) = (camera_query.get_single(), globe_query.get_single())
+    else {
+        return;
+    };
+    let Some(ray) = camera.viewport_to_world(camera_transform, cursor) else {
+        return;
+    };
+
+    //nearest point where the ray meets a sphere of radius 1 around the globe
+    let to_center = globe_transform.translation() - ray.origin;
+    let along = to_center.dot(*ray.direction);
+    let miss = to_center.length_squared() - along * along;
+    if miss > 1. {
+        return;
+    }
+    let hit = ray.get_point(along - (1. - miss).sqrt());
+    let Some(position) = globe_transform.affine().inverse().transform_point3(hit).try_normalize() else {
+        return;
+    };
+
+    let settings = &config.hotspots;
+    commands.spawn(Hotspot {
+        position,
+        strength: settings.strength,
+        radius: settings.radius,
+        lifetime: settings.lifetime,
+        born_myr: clock.time_myr,
+        born_tick: clock.ticks,
+    });
+}
+
+// draws a ring around every active hotspot, turning with the globe
+pub fn draw_hotspots(
+    mut gizmos: Gizmos,
+    clock: Res<SimulationClock>,
+    grid: Res<Grid>,
+    columns: Res<Columns>,
+    hotspots: Query<&Hotspot>,
+    globe_query: Query<&GlobalTransform, With<Shape>>,
+) {
+    let Ok(globe_transform) = globe_query.get_single() else {
+        return;
+    };
+    for hotspot in hotspots.iter().filter(|hotspot| hotspot.is_active(&clock)) {
+        let height = if columns.fits(&grid) {
+            columns.height(grid.cell_from_direction(hotspot.position))
+        } else {
+            1.
+        };
+        let center = globe_transform.transform_point(hotspot.position * (height + OVERLAY_LIFT) * hotspot.radius.cos());
+        let Ok(normal) = Direction3d::new(globe_transform.affine().transform_vector3(hotspot.position)) else {
+            continue;
+        };
+        gizmos.circle(center, normal, (height + OVERLAY_LIFT) * hotspot.radius.sin(), Color::ORANGE_RED);
+    }
+}
+
+// forgets the hotspots that rose in the ticks the clock has been stepped back past, run after step_back
+pub fn rewind_hotspots(
+    mut commands: Commands,
+    clock: Res<SimulationClock>,
+    hotspots: Query<(Entity, &Hotspot)>,
+) {
+    for (entity, hotspot) in &hotspots {
+        if hotspot.born_tick > clock.ticks {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::{
+        columns::RockType,
+        grid::GridKind,
+        plates::{advance_plates, Plate, Plates},
+    };
+
+    // one plate of old ocean floor turning about the y axis, so the ground at +z moves towards +x, over a hotspot
+    // at +z that stays active for the whole test
+    fn plate_over_hotspot() -> World {
+        let mut config = WorldConfig { grid: GridKind::CubeSphere, cube_face_cells: 32, ..default() };
+        config.hotspots.spawn_rate = 0.;
+        let grid = Grid::from_config(&config);
+        let mut columns = Columns::new(grid.cell_count(), RockType::Basalt, &config);
+        columns.crust_age.fill(100.);
+        let plates = vec![Plate { id: 0, pole: Vec3::Y, angular_velocity: 0.01, ..default() }];
+
+        let mut world = World::new();
+        world.spawn(Hotspot {
+            position: Vec3::Z,
+            strength: config.hotspots.strength,
+            radius: config.hotspots.radius,
+            lifetime: 1000.,
+            born_myr: 0.,
+            born_tick: 0,
+        });
+        world.insert_resource(config);
+        world.insert_resource(grid);
+        world.insert_resource(columns);
+        world.insert_resource(Plates { plates });
+        world.init_resource::<SimulationClock>();
+        world
+    }
+
+    #[test]
+    fn moving_plate_leaves_a_chain_that_ages_downstream() {
+        let mut world = plate_over_hotspot();
+        for _ in 0..60 {
+            world.run_system_once(advance_plates);
+            world.run_system_once(hotspot_volcanism);
+        }
+
+        //walk from the hotspot the way the plate moves, the volcanoes get older the further they have been carried
+        let config = world.resource::<WorldConfig>().clone();
+        let (grid, columns) = (world.resource::<Grid>(), world.resource::<Columns>());
+        let track = |side: f32| {
+            let mut cells: Vec<usize> = (0..40)
+                .map(|step| {
+                    let angle = side * step as f32 * grid.cell_angle() / 2.;
+                    grid.cell_from_direction(Vec3::new(angle.sin(), 0., angle.cos()))
+                })
+                .collect();
+            cells.dedup();
+            cells
+        };
+        let volcanic = |cell: usize| columns.crust_thickness[cell] > config.oceanic_crust_thickness * 1.1;
+        let chain: Vec<usize> = track(1.).into_iter().take_while(|&cell| volcanic(cell)).collect();
+        assert!(chain.len() >= 8, "a chain of only {} cells", chain.len());
+        for pair in chain.windows(2) {
+            assert!(columns.crust_age[pair[1]] >= columns.crust_age[pair[0]]);
+        }
+        let (youngest, oldest) = (columns.crust_age[chain[0]], columns.crust_age[chain[chain.len() - 1]]);
+        assert_eq!(youngest, config.hotspots.swell_age);
+        assert!(oldest > youngest + 30., "the chain only spans {} to {} Myr", youngest, oldest);
+
+        //nothing has been carried the other way yet
+        assert!(track(-1.).iter().skip(2).all(|&cell| !volcanic(cell)));
+    }
+}
@@ -0,0 +1,399 @@
+// Icosahedral grid
+//
+// The globe is an icosahedron whose 20 faces (supertriangles) are each subdivided into res x res small triangles,
+// with res = 2^subdivisions. Every vertex is a cell. Vertices on the edges and corners of the supertriangles are
+// shared by the faces that meet there, so each one has a single id and a single height no matter which face
+// looks at it.
+//
+//icosahedron net array reference
+//                                 /\  /\  /\  /\  /\   [0,0]-[0,4]
+//      /\  /\  /\  /\  /\        /__\/__\/__\/__\/__\
+//     /__\/__\/__\/__\/__\       \  /\  /\  /\  /\  /
+//    /\  /\  /\  /\  /\  /        \/  \/  \/  \/  \/   [1,0]-[1,4]
+//   /__\/__\/__\/__\/__\/  ==>  /\  /\  /\  /\  /\     [2,0]-[2,4]
+//   \  /\  /\  /\  /\  /       /__\/__\/__\/__\/__\
+//    \/  \/  \/  \/  \/        \  /\  /\  /\  /\  /
+//                               \/  \/  \/  \/  \/     [3,0]-[3,4]
+//
+//coords of adjacent supertri to super tri with index [i,j] by i (aka row):
+// i  left          right         vertical
+// 0: [0, (j-1)%5], [0, (j+1)%5], [1, j]
+// 1: [2, j],       [2, (j+1)%5], [0, j]
+// 2: [1, (j-1)%5], [1, j],       [3, j]
+// 3: [3, (j-1)%5], [3, (j+1)%5], [2, j]
+//
+//each supertri has corners c (the point of the triangle), b and a (the ends of its horizontal edge):
+//
+//           c                 b________________a
+//          /\                  \  /\  /\  /\  /
+//         /__\                  \/__\/__\/__\/
+//        /\  /\                  \  /\  /\  /
+//       /__\/__\                  \/__\/__\/
+//      /\  /\  /\                  \  /\  /
+//     /__\/__\/__\                  \/__\/
+//    /\  /\  /\  /\                  \  /
+//   /__\/__\/__\/__\                  \/
+//  b                a                 c
+//
+//a point on a supertri is tracked with 2 coordinates: a is the distance from the left edge (c-b), b is the distance
+//from the right edge (c-a), and c = res - a - b is the distance from the horizontal edge (b-a)
+//the left, right and vertical neighbors in the table above are the ones across the c-b, c-a and b-a edges
+
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology},
+};
+
+use crate::grid::{spherical_triangle_area, SphereGrid};
+
+// corner ids: 0 is the top, 1-5 the top ring, 6-10 the bottom ring, 11 the bottom
+const TOP: usize = 0;
+const BOTTOM: usize = 11;
+fn top_ring(k: usize) -> usize {
+    1 + k % 5
+}
+fn bottom_ring(k: usize) -> usize {
+    6 + k % 5
+}
+
+// [c, b, a] corner ids of supertri [i, j], chosen so the edges line up with the adjacency table
+fn supertri_corners(i: usize, j: usize) -> [usize; 3] {
+    match i {
+        0 => [TOP, top_ring(j), top_ring(j + 1)],
+        1 => [bottom_ring(j), top_ring(j), top_ring(j + 1)],
+        2 => [top_ring(j), bottom_ring(j + 4), bottom_ring(j)],
+        _ => [BOTTOM, bottom_ring(j + 4), bottom_ring(j)],
+    }
+}
+
+// the supertris across the left (c-b), right (c-a) and vertical (b-a) edges of supertri [i, j]
+fn supertri_neighbors(i: usize, j: usize) -> [[usize; 2]; 3] {
+    let prev = (j + 4) % 5;
+    let next = (j + 1) % 5;
+    match i {
+        0 => [[0, prev], [0, next], [1, j]],
+        1 => [[2, j], [2, next], [0, j]],
+        2 => [[1, prev], [1, j], [3, j]],
+        _ => [[3, prev], [3, next], [2, j]],
+    }
+}
+
+fn corner_direction(corner: usize) -> Vec3 {
+    let ring_y = 1. / 5f32.sqrt();
+    let ring_radius = 2. / 5f32.sqrt();
+    let angle = |k: f32| 2. * std::f32::consts::PI * k / 5.;
+    match corner {
+        TOP => Vec3::Y,
+        BOTTOM => Vec3::NEG_Y,
+        1..=5 => {
+            let k = (corner - 1) as f32;
+            Vec3::new(angle(k).cos() * ring_radius, ring_y, angle(k).sin() * ring_radius)
+        }
+        _ => {
+            //the bottom ring sits halfway between the top ring's corners
+            let k = (corner - 6) as f32 + 0.5;
+            Vec3::new(angle(k).cos() * ring_radius, -ring_y, angle(k).sin() * ring_radius)
+        }
+    }
+}
+
+pub struct IcoGrid {
+    // small triangles along each edge of a supertri
+    res: usize,
+
+    // index of the edge between two corners, in the order they were first found
+    edge_index: [[usize; 12]; 12],
+
+    directions: Vec<Vec3>,
+    neighbors: Vec<Vec<u32>>,
+    areas: Vec<f32>,
+    face_centers: [Vec3; 20],
+
+    // three vertex ids per small triangle, wound counter clockwise seen from outside
+    triangles: Vec<u32>,
+}
+
+impl IcoGrid {
+    pub fn new(subdivisions: u32) -> Self {
+        let res = 1usize << subdivisions;
+
+        let mut edge_index = [[usize::MAX; 12]; 12];
+        let mut edge_count = 0;
+        let mut face_centers = [Vec3::ZERO; 20];
+        for (face, center) in face_centers.iter_mut().enumerate() {
+            let corners = supertri_corners(face / 5, face % 5);
+            for (p, q) in [(corners[0], corners[1]), (corners[0], corners[2]), (corners[1], corners[2])] {
+                if edge_index[p][q] == usize::MAX {
+                    edge_index[p][q] = edge_count;
+                    edge_index[q][p] = edge_count;
+                    edge_count += 1;
+                }
+            }
+            *center = corners.iter().map(|&corner| corner_direction(corner)).sum::<Vec3>().normalize();
+        }
+
+        let mut grid = Self {
+            res,
+            edge_index,
+            directions: Vec::new(),
+            neighbors: Vec::new(),
+            areas: Vec::new(),
+            face_centers,
+            triangles: Vec::new(),
+        };
+
+        let cell_count = 10 * res * res + 2;
+        grid.directions = vec![Vec3::ZERO; cell_count];
+
+        //the first (face, a, b) each vertex was found at, neighbor lookup starts from there
+        let mut home = vec![None; cell_count];
+        for face in 0..20 {
+            for a in 0..=res {
+                for b in 0..=(res - a) {
+                    let id = grid.vertex(face, a, b);
+                    grid.directions[id] = grid.local_direction(face, a, b);
+                    home[id].get_or_insert((face, a, b));
+                }
+            }
+        }
+
+        grid.neighbors = home
+            .iter()
+            .map(|home| {
+                let (face, a, b) = home.expect("every vertex is on a supertri");
+                grid.find_neighbors(face, a, b)
+            })
+            .collect();
+
+        for face in 0..20 {
+            for a in 0..res {
+                for b in 0..(res - a) {
+                    grid.push_triangle([grid.vertex(face, a, b), grid.vertex(face, a + 1, b), grid.vertex(face, a, b + 1)]);
+                    if a + b + 1 < res {
+                        grid.push_triangle([
+                            grid.vertex(face, a + 1, b),
+                            grid.vertex(face, a + 1, b + 1),
+                            grid.vertex(face, a, b + 1),
+                        ]);
+                    }
+                }
+            }
+        }
+
+        //every small triangle gives a third of its area to each of its corners
+        grid.areas = vec![0.; cell_count];
+        for corners in grid.triangles.chunks(3) {
+            let [p, q, r] = [0, 1, 2].map(|k| grid.directions[corners[k] as usize]);
+            let share = spherical_triangle_area(p, q, r) / 3.;
+            for &corner in corners {
+                grid.areas[corner as usize] += share;
+            }
+        }
+
+        grid
+    }
+
+    // id of the point a from the left edge and b from the right edge of a supertri (numbered 5 * row + column)
+    // points on an edge or corner get the same id from every supertri that shares them
+    pub fn vertex(&self, face: usize, a: usize, b: usize) -> usize {
+        let res = self.res;
+        let c = res - a - b;
+        let [corner_c, corner_b, corner_a] = supertri_corners(face / 5, face % 5);
+
+        //corners
+        if a == res {
+            return corner_a;
+        }
+        if b == res {
+            return corner_b;
+        }
+        if c == res {
+            return corner_c;
+        }
+
+        //edges, counted from the lower numbered corner so both supertris agree on the position along it
+        let on_edge = match (a, b, c) {
+            (_, _, 0) => Some((corner_b, b, corner_a, a)),
+            (0, _, _) => Some((corner_c, c, corner_b, b)),
+            (_, 0, _) => Some((corner_c, c, corner_a, a)),
+            _ => None,
+        };
+        if let Some((p, weight_p, q, weight_q)) = on_edge {
+            let steps = if p > q { weight_p } else { weight_q };
+            return 12 + self.edge_index[p][q] * (res - 1) + steps - 1;
+        }
+
+        //inside, "magic formula" from the old get_height, shifted off the edges
+        let (a, b) = (a - 1, b - 1);
+        let interior = a + (b + a) * (b + a + 1) / 2;
+        12 + 30 * (res - 1) + face * (res - 1) * (res - 2) / 2 + interior
+    }
+
+    fn local_direction(&self, face: usize, a: usize, b: usize) -> Vec3 {
+        let [corner_c, corner_b, corner_a] = supertri_corners(face / 5, face % 5);
+        let c = self.res - a - b;
+        (corner_direction(corner_a) * a as f32 + corner_direction(corner_b) * b as f32 + corner_direction(corner_c) * c as f32)
+            .normalize()
+    }
+
+    // the (a, b) of a corner or edge vertex of one supertri on the supertri next to it
+    fn local_on(&self, from: usize, a: usize, b: usize, to: usize) -> (usize, usize) {
+        let res = self.res;
+        let [from_c, from_b, from_a] = supertri_corners(from / 5, from % 5);
+        let weights = [(from_a, a), (from_b, b), (from_c, res - a - b)];
+        let weight_of = |corner: usize| weights.iter().find(|(id, _)| *id == corner).map_or(0, |(_, weight)| *weight);
+
+        let [_, to_b, to_a] = supertri_corners(to / 5, to % 5);
+        (weight_of(to_a), weight_of(to_b))
+    }
+
+    // walks from supertri to supertri with the adjacency table until every supertri touching the vertex has been
+    // seen, collecting the vertices next to it on each of them
+    fn find_neighbors(&self, face: usize, a: usize, b: usize) -> Vec<u32> {
+        let res = self.res;
+        let mut visited = vec![(face, a, b)];
+        let mut next = 0;
+        while next < visited.len() {
+            let (face, a, b) = visited[next];
+            next += 1;
+
+            let across = supertri_neighbors(face / 5, face % 5);
+            let edges = [a == 0, b == 0, a + b == res];
+            for (edge, &on_edge) in edges.iter().enumerate() {
+                let other = across[edge][0] * 5 + across[edge][1];
+                if on_edge && !visited.iter().any(|(seen, _, _)| *seen == other) {
+                    let (other_a, other_b) = self.local_on(face, a, b, other);
+                    visited.push((other, other_a, other_b));
+                }
+            }
+        }
+
+        let mut neighbors = Vec::with_capacity(6);
+        for (face, a, b) in visited {
+            let (a, b) = (a as isize, b as isize);
+            for (da, db) in [(1, 0), (-1, 0), (0, 1), (0, -1), (1, -1), (-1, 1)] {
+                let (na, nb) = (a + da, b + db);
+                if na >= 0 && nb >= 0 && na + nb <= res as isize {
+                    neighbors.push(self.vertex(face, na as usize, nb as usize) as u32);
+                }
+            }
+        }
+        neighbors.sort_unstable();
+        neighbors.dedup();
+        neighbors
+    }
+
+    fn push_triangle(&mut self, corners: [usize; 3]) {
+        let [p0, p1, p2] = corners.map(|id| self.directions[id]);
+        let outward = (p1 - p0).cross(p2 - p0).dot(p0) > 0.;
+        let order = if outward { [0, 1, 2] } else { [0, 2, 1] };
+        self.triangles.extend(order.map(|k| corners[k] as u32));
+    }
+}
+
+impl SphereGrid for IcoGrid {
+    fn cell_count(&self) -> usize {
+        self.directions.len()
+    }
+
+    fn direction(&self, cell: usize) -> Vec3 {
+        self.directions[cell]
+    }
+
+    fn neighbors(&self, cell: usize) -> &[u32] {
+        &self.neighbors[cell]
+    }
+
+    // angle between two neighboring cells, roughly the same everywhere on this grid
+    fn cell_angle(&self) -> f32 {
+        let spacing = self.neighbors[0].iter().map(|&n| self.directions[0].angle_between(self.directions[n as usize]));
+        spacing.sum::<f32>() / self.neighbors[0].len() as f32
+    }
+
+    fn cell_area(&self, cell: usize) -> f32 {
+        self.areas[cell]
+    }
+
+    fn cell_from_direction(&self, dir: Vec3) -> usize {
+        let dir = dir.normalize();
+
+        //each supertri is exactly the part of the sphere closest to its center
+        let face = (0..20)
+            .max_by(|&f, &g| self.face_centers[f].dot(dir).total_cmp(&self.face_centers[g].dot(dir)))
+            .unwrap_or(0);
+
+        //where the direction goes through the flat supertri, as a mix of its corners
+        let [corner_c, corner_b, corner_a] = supertri_corners(face / 5, face % 5).map(corner_direction);
+        let normal = (corner_b - corner_c).cross(corner_a - corner_c);
+        let point = dir * (normal.dot(corner_c) / normal.dot(dir));
+        let area = normal.length_squared();
+        let weight_a = (corner_b - corner_c).cross(point - corner_c).dot(normal) / area;
+        let weight_b = (point - corner_c).cross(corner_a - corner_c).dot(normal) / area;
+
+        let res = self.res as f32;
+        let a = (weight_a * res).round().clamp(0., res) as usize;
+        let b = ((weight_b * res).round().clamp(0., res) as usize).min(self.res - a);
+
+        //normalizing bends the grid a little, finish by stepping to whichever neighbor is closer
+        let mut cell = self.vertex(face, a, b);
+        loop {
+            let closer = self.neighbors[cell]
+                .iter()
+                .map(|&n| n as usize)
+                .max_by(|&m, &n| self.directions[m].dot(dir).total_cmp(&self.directions[n].dot(dir)))
+                .filter(|&n| self.directions[n].dot(dir) > self.directions[cell].dot(dir));
+            match closer {
+                Some(n) => cell = n,
+                None => return cell,
+            }
+        }
+    }
+
+    fn positions(&self, heights: &[f32]) -> Vec<[f32; 3]> {
+        self.directions.iter().zip(heights).map(|(dir, height)| (*dir * *height).to_array()).collect()
+    }
+
+    // indexed, every vertex is stored once
+    fn mesh(&self, heights: &[f32]) -> Mesh {
+        let norms: Vec<[f32; 3]> = self.directions.iter().map(|dir| dir.to_array()).collect();
+
+        Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, self.positions(heights))
+            .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, norms)
+            .with_inserted_indices(Indices::U32(self.triangles.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_invariants() {
+        for subdivisions in 0..=4 {
+            let grid = IcoGrid::new(subdivisions);
+            let res = 1 << subdivisions;
+            assert_eq!(grid.cell_count(), 10 * res * res + 2, "level {subdivisions}");
+
+            for cell in 0..grid.cell_count() {
+                let neighbors = grid.neighbors(cell);
+                let count = neighbors.len();
+                assert!(matches!(count, 5 | 6), "level {subdivisions} cell {cell} has {count} neighbors");
+                for &neighbor in neighbors {
+                    assert_ne!(neighbor as usize, cell, "level {subdivisions} cell {cell} neighbors itself");
+                    let back = grid.neighbors(neighbor as usize).contains(&(cell as u32));
+                    assert!(back, "level {subdivisions} cell {cell} -> {neighbor} is one way");
+                }
+                assert_eq!(grid.cell_from_direction(grid.direction(cell)), cell, "level {subdivisions} cell {cell}");
+            }
+
+            //only the 12 corners of the icosahedron have five neighbors
+            let pentagons = (0..grid.cell_count()).filter(|&cell| grid.neighbors(cell).len() == 5).count();
+            assert_eq!(pentagons, 12, "level {subdivisions}");
+
+            let area: f64 = (0..grid.cell_count()).map(|cell| grid.cell_area(cell) as f64).sum();
+            let sphere = 4. * std::f64::consts::PI;
+            assert!((area - sphere).abs() < 1e-4 * sphere, "level {subdivisions} areas add up to {area}");
+        }
+    }
+}
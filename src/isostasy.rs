@@ -0,0 +1,273 @@
+// Isostasy
+//
+// The crust floats on the mantle, and every column of it settles to the height where it displaces its own weight of
+// mantle. Thick or light crust stands high and thin or dense crust sits low. Eroding the top off a mountain range
+// unloads it and it rises back part of the way, while the sediment piling up in a basin pushes it down.
+//
+// Heights are measured from freshly formed crust, which sits in equilibrium at the heights in WorldConfig. Under the
+// Airy model every column has the same density and floats on a root as deep as it needs, so extra crust thickness
+// raises the surface by the share of it the mantle does not make up for (about a fifth for granite). Ocean floor also
+// sinks along the cooling curve of seafloor spreading as it ages. Under the Pratt model every column reaches down to
+// the same compensation depth and stands higher the lighter it is. Only the density of the crust matters then, and
+// ocean floor sinks because old oceanic crust is denser. Sediment weighs its column down the same way under both.
+//
+// Columns don't jump to equilibrium, each tick they close part of the gap, set by the relaxation time. Cells on
+// convergent boundaries are held down or pushed up by the plates themselves and are left alone. With flexure on, Airy
+// columns don't float on their own either, they settle to the deflection of the bent plate (see flexure.rs).
+
+use bevy::prelude::*;
+
+use crate::{
+    boundaries::{BoundaryKind, PlateBoundaries},
+    columns::{Columns, RockType},
+    config::WorldConfig,
+    flexure::Flexure,
+    grid::Grid,
+};
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum IsostasyModel
+{
+    // same density everywhere, compensated by the depth of the crust's root
+    #[default]
+    Airy,
+
+    // same compensation depth everywhere, compensated by the density of the column
+    Pratt,
+}
+
+// Settings for isostasy, part of WorldConfig. The crust and mantle densities are the ones subduction uses
+#[derive(Reflect, Clone, Debug)]
+pub struct IsostasySettings {
+    pub enabled: bool,
+    pub model: IsostasyModel,
+
+    pub sediment_density: f32,
+
+    // depth every column reaches down to under the Pratt model, about 100 km on Earth
+    pub compensation_depth: f32,
+
+    // million years it takes a column to get about two thirds of the way back to equilibrium, far slower than on
+    // Earth so the rebound can be watched, 0 settles it within a tick
+    pub relaxation_time: f32,
+}
+
+impl Default for IsostasySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            model: IsostasyModel::Airy,
+            sediment_density: 2.3,
+            compensation_depth: 0.8,
+            relaxation_time: 1.,
+        }
+    }
+}
+
+// how far a cell's load pushes it down under the Airy model if it floats on its own, measured from fresh crust
+// the crust thickened past fresh crust and the sediment on top both displace their weight of mantle
+pub fn local_deflection(config: &WorldConfig, columns: &Columns, cell: usize) -> f32 {
+    let densities = &config.orogeny;
+    let rock_type = columns.rock_type[cell];
+    let (_, fresh_thickness) = config.fresh_crust(rock_type);
+    let thickening = columns.crust_thickness[cell] - fresh_thickness;
+    let sediment = config.isostasy.sediment_density * columns.sediment[cell];
+    (densities.density(rock_type, 0.) * thickening + sediment) / densities.mantle_density
+}
+
+// height of the ground where a cell's column would float at rest under the Airy model, pushed down by a deflection
+fn airy_height(config: &WorldConfig, columns: &Columns, cell: usize, deflection: f32) -> f32 {
+    let rock_type = columns.rock_type[cell];
+    let (fresh_height, fresh_thickness) = config.fresh_crust(rock_type);
+    let cooling = if rock_type == RockType::Basalt && config.spreading.enabled {
+        config.spreading.cooling_depth(columns.crust_age[cell])
+    } else {
+        0.
+    };
+    let thickening = columns.crust_thickness[cell] - fresh_thickness;
+    fresh_height + thickening + columns.sediment[cell] - cooling - deflection
+}
+
+// height of the ground where a cell's column would float at rest on its own
+pub fn equilibrium_height(config: &WorldConfig, columns: &Columns, cell: usize) -> f32 {
+    match config.isostasy.model {
+        IsostasyModel::Airy => airy_height(config, columns, cell, local_deflection(config, columns, cell)),
+        IsostasyModel::Pratt => {
+            let densities = &config.orogeny;
+            let rock_type = columns.rock_type[cell];
+            let (fresh_height, _) = config.fresh_crust(rock_type);
+
+            //sediment is a load lying on the crust, and crust stops getting denser once it has cooled
+            let sediment = columns.sediment[cell] * (1. - config.isostasy.sediment_density / densities.mantle_density);
+            let age = columns.crust_age[cell].min(config.spreading.cooling_limit);
+            let density_ratio = densities.density(rock_type, 0.) / densities.density(rock_type, age);
+            fresh_height + config.isostasy.compensation_depth * (density_ratio - 1.) + sediment
+        }
+    }
+}
+
+// moves every column part of the way to equilibrium, run on the fixed timestep after everything that loads or
+// unloads the crust
+pub fn isostasy(
+    config: Res<WorldConfig>,
+    grid: Res<Grid>,
+    boundaries: Res<PlateBoundaries>,
+    flexure: Res<Flexure>,
+    mut columns: ResMut<Columns>,
+) {
+    let settings = &config.isostasy;
+    if !settings.enabled || !columns.fits(&grid) {
+        return;
+    }
+
+    let settle = if settings.relaxation_time > 0. {
+        1. - (-config.myr_per_tick / settings.relaxation_time).exp()
+    } else {
+        1.
+    };
+    let flexed = config.flexure.enabled
+        && settings.model == IsostasyModel::Airy
+        && flexure.deflection.len() == grid.cell_count();
+    for cell in 0..grid.cell_count() {
+        if boundaries.at(cell).iter().any(|edge| edge.kind == BoundaryKind::Convergent) {
+            continue;
+        }
+        let target = if flexed {
+            airy_height(&config, &columns, cell, flexure.deflection[cell])
+        } else {
+            equilibrium_height(&config, &columns, cell)
+        };
+        columns.bedrock[cell] += (target - columns.height(cell)) * settle;
+    }
+}
+
+// gives the relief of a new globe the roots that hold it up under the Airy model, so it doesn't sink away in the
+// first few ticks
+// the crust is never thinned below half its fresh thickness, lows deeper than that settle
+pub fn isostasy_setup(
+    config: Res<WorldConfig>,
+    grid: Res<Grid>,
+    mut columns: ResMut<Columns>,
+) {
+    if !config.isostasy.enabled || config.isostasy.model != IsostasyModel::Airy || !columns.fits(&grid) {
+        return;
+    }
+
+    for cell in 0..grid.cell_count() {
+        let rock_type = columns.rock_type[cell];
+        let (_, fresh_thickness) = config.fresh_crust(rock_type);
+        let buoyancy = 1. - config.orogeny.density(rock_type, 0.) / config.orogeny.mantle_density;
+        let excess = columns.height(cell) - equilibrium_height(&config, &columns, cell);
+        columns.crust_thickness[cell] = (columns.crust_thickness[cell] + excess / buoyancy).max(fresh_thickness / 2.);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::grid::GridKind;
+
+    // fresh ocean floor everywhere, with isostasy settling within a tick and no plate bending under the load
+    fn ocean_world(model: IsostasyModel) -> (WorldConfig, Grid, Columns) {
+        let mut config = WorldConfig { grid: GridKind::Icosahedral, ico_subdivisions: 3, ..default() };
+        config.isostasy.model = model;
+        config.isostasy.relaxation_time = 0.;
+        config.flexure.enabled = false;
+        let grid = Grid::from_config(&config);
+        let columns = Columns::new(grid.cell_count(), RockType::Basalt, &config);
+        (config, grid, columns)
+    }
+
+    // drops every column to the same height and lets them all float back up
+    fn settle(config: &WorldConfig, columns: &Columns) -> Columns {
+        let mut world = World::new();
+        world.insert_resource(config.clone());
+        world.insert_resource(Grid::from_config(config));
+        let mut columns = columns.clone();
+        columns.bedrock.fill(0.5);
+        world.insert_resource(columns);
+        world.init_resource::<PlateBoundaries>();
+        world.init_resource::<Flexure>();
+        world.run_system_once(isostasy);
+        world.remove_resource::<Columns>().unwrap()
+    }
+
+    // a few cells far apart from each other, one for each kind of column
+    struct Samples {
+        basalt: usize,
+        thick_basalt: usize,
+        old_basalt: usize,
+        granite: usize,
+        thick_granite: usize,
+    }
+
+    const THICKENING: f32 = 0.1;
+
+    fn samples(config: &WorldConfig, grid: &Grid, columns: &mut Columns) -> Samples {
+        let cell = |dir: Vec3| grid.cell_from_direction(dir);
+        let samples = Samples {
+            basalt: cell(Vec3::Z),
+            thick_basalt: cell(Vec3::X),
+            old_basalt: cell(Vec3::NEG_Z),
+            granite: cell(Vec3::Y),
+            thick_granite: cell(Vec3::NEG_X),
+        };
+        columns.crust_thickness[samples.thick_basalt] += THICKENING;
+        columns.crust_age[samples.old_basalt] = 50.;
+        columns.new_crust(samples.granite, RockType::Granite, config);
+        columns.new_crust(samples.thick_granite, RockType::Granite, config);
+        columns.crust_thickness[samples.thick_granite] += THICKENING;
+        samples
+    }
+
+    #[test]
+    fn airy_columns_stand_higher_the_thicker_and_lighter_they_are() {
+        let (config, grid, mut columns) = ocean_world(IsostasyModel::Airy);
+        let at = samples(&config, &grid, &mut columns);
+        let columns = settle(&config, &columns);
+        let height = |cell| columns.height(cell);
+
+        //fresh crust floats where it formed
+        assert!((height(at.basalt) - config.oceanic_crust_height).abs() < 1e-6);
+        assert!((height(at.granite) - config.continental_crust_height).abs() < 1e-6);
+
+        //thickened crust rises by the share of the extra thickness the mantle doesn't make up for
+        let densities = &config.orogeny;
+        let rise = |cell, fresh, rock_type| {
+            let share = 1. - densities.density(rock_type, 0.) / densities.mantle_density;
+            let rise: f32 = height(cell) - fresh;
+            assert!((rise - THICKENING * share).abs() < 1e-5, "rose {} instead of {}", rise, THICKENING * share);
+            rise
+        };
+        let basalt_rise = rise(at.thick_basalt, config.oceanic_crust_height, RockType::Basalt);
+        let granite_rise = rise(at.thick_granite, config.continental_crust_height, RockType::Granite);
+        assert!(basalt_rise > 0.);
+
+        //the same extra thickness lifts the lighter granite further
+        assert!(granite_rise > basalt_rise);
+
+        //old ocean floor is colder and denser and sits lower
+        assert!(height(at.old_basalt) < height(at.basalt));
+    }
+
+    #[test]
+    fn pratt_columns_stand_higher_the_lighter_they_are() {
+        let (config, grid, mut columns) = ocean_world(IsostasyModel::Pratt);
+        let at = samples(&config, &grid, &mut columns);
+        columns.crust_age[at.thick_basalt] = 20.;
+        let columns = settle(&config, &columns);
+        let height = |cell| columns.height(cell);
+
+        assert!((height(at.basalt) - config.oceanic_crust_height).abs() < 1e-6);
+        assert!((height(at.granite) - config.continental_crust_height).abs() < 1e-6);
+        assert!(height(at.granite) > height(at.basalt));
+
+        //every column reaches down to the same depth, so only its density sets its height, and ocean floor sinks
+        //the further the older and denser it gets
+        assert!(height(at.old_basalt) < height(at.thick_basalt));
+        assert!(height(at.thick_basalt) < height(at.basalt));
+        assert!((height(at.thick_granite) - height(at.granite)).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,188 @@
+// Looking at the column layers
+//
+// The globe's shape always follows the height of the ground, and one layer of the Columns at a time colors it. The
+// cycle key steps through the layers, and the export key writes the layer being shown to a CSV file in the save
+// folder, one row per cell with its latitude and longitude, so it can be plotted or checked somewhere else. The file
+// is written on the IO task pool.
+
+use std::{io::Write, path::Path};
+
+use bevy::prelude::*;
+
+use crate::{
+    columns::{Columns, RockType},
+    config::WorldConfig,
+    grid::Grid,
+    saving::{timestamped_name, ExportTask},
+    settings::Settings,
+    toast::ShowToast,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ColumnLayer
+{
+    // bedrock plus sediment, what the globe's shape shows
+    #[default]
+    Height,
+    Bedrock,
+    Sediment,
+    Water,
+    SuspendedSediment,
+    CrustAge,
+    CrustThickness,
+    RockType,
+    PlateId,
+}
+
+impl ColumnLayer {
+    const ALL: [ColumnLayer; 9] = [
+        ColumnLayer::Height,
+        ColumnLayer::Bedrock,
+        ColumnLayer::Sediment,
+        ColumnLayer::Water,
+        ColumnLayer::SuspendedSediment,
+        ColumnLayer::CrustAge,
+        ColumnLayer::CrustThickness,
+        ColumnLayer::RockType,
+        ColumnLayer::PlateId,
+    ];
+
+    // used in toasts and as the value column of exported files
+    pub fn name(self) -> &'static str {
+        match self {
+            ColumnLayer::Height => "height",
+            ColumnLayer::Bedrock => "bedrock",
+            ColumnLayer::Sediment => "sediment",
+            ColumnLayer::Water => "water",
+            ColumnLayer::SuspendedSediment => "suspended_sediment",
+            ColumnLayer::CrustAge => "crust_age",
+            ColumnLayer::CrustThickness => "crust_thickness",
+            ColumnLayer::RockType => "rock_type",
+            ColumnLayer::PlateId => "plate_id",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|layer| *layer == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    // one value per cell, rock types are 0 for basalt and 1 for granite
+    pub fn values(self, columns: &Columns) -> Vec<f32> {
+        match self {
+            ColumnLayer::Height => columns.heights(),
+            ColumnLayer::Bedrock => columns.bedrock.clone(),
+            ColumnLayer::Sediment => columns.sediment.clone(),
+            ColumnLayer::Water => columns.water.clone(),
+            ColumnLayer::SuspendedSediment => columns.suspended_sediment.clone(),
+            ColumnLayer::CrustAge => columns.crust_age.clone(),
+            ColumnLayer::CrustThickness => columns.crust_thickness.clone(),
+            ColumnLayer::RockType => columns.rock_type.iter().map(|rock| rock_index(*rock) as f32).collect(),
+            ColumnLayer::PlateId => columns.plate_id.iter().map(|id| *id as f32).collect(),
+        }
+    }
+}
+
+// Which layer colors the globe
+#[derive(Resource, Default)]
+pub struct DisplayLayer(pub ColumnLayer);
+
+fn rock_index(rock: RockType) -> u8 {
+    match rock {
+        RockType::Basalt => 0,
+        RockType::Granite => 1,
+    }
+}
+
+// blue below sea level getting darker with depth, green to brown above it
+pub fn height_color(height: f32, sea_level: f32) -> Color {
+    if height < sea_level {
+        let depth = ((sea_level - height) / 0.05).clamp(0., 1.);
+        Color::rgb(0.1, 0.3 - 0.2 * depth, 0.8 - 0.4 * depth)
+    } else {
+        let elevation = ((height - sea_level) / 0.05).clamp(0., 1.);
+        Color::rgb(0.2 + 0.4 * elevation, 0.6 - 0.2 * elevation, 0.2)
+    }
+}
+
+// vertex colors of the globe mesh for a layer, continuous layers run from dark blue at their lowest value to yellow
+// at their highest
+pub fn layer_colors(layer: ColumnLayer, columns: &Columns, config: &WorldConfig) -> Vec<[f32; 4]> {
+    let colors: Vec<Color> = match layer {
+        ColumnLayer::Height => {
+            columns.heights().into_iter().map(|height| height_color(height, config.erosion.sea_level)).collect()
+        }
+        ColumnLayer::RockType => columns
+            .rock_type
+            .iter()
+            .map(|rock| match rock {
+                RockType::Basalt => Color::rgb(0.25, 0.25, 0.3),
+                RockType::Granite => Color::rgb(0.8, 0.6, 0.55),
+            })
+            .collect(),
+        //the golden angle keeps the hues of plates with nearby ids far apart
+        ColumnLayer::PlateId => {
+            columns.plate_id.iter().map(|id| Color::hsl((*id as f32 * 137.5) % 360., 0.55, 0.55)).collect()
+        }
+        _ => {
+            let values = layer.values(columns);
+            let low = values.iter().copied().fold(f32::INFINITY, f32::min);
+            let high = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            let range = if high > low { high - low } else { 1. };
+            values
+                .into_iter()
+                .map(|value| {
+                    let t = (value - low) / range;
+                    Color::rgb(0.1 + 0.85 * t, 0.1 + 0.75 * t, 0.4 - 0.3 * t)
+                })
+                .collect()
+        }
+    };
+    colors.into_iter().map(|color| color.as_linear_rgba_f32()).collect()
+}
+
+// writes one layer as CSV from its values and the directions of their cells, latitude and longitude are in degrees
+// with longitude 0 along +x
+fn write_layer_csv(path: &Path, layer: ColumnLayer, values: &[f32], directions: &[Vec3]) -> Result<(), String> {
+    let mut text = Vec::new();
+    let mut write = || -> std::io::Result<()> {
+        writeln!(text, "cell,latitude,longitude,{}", layer.name())?;
+        for (cell, (value, dir)) in values.iter().zip(directions).enumerate() {
+            let latitude = dir.y.clamp(-1., 1.).asin().to_degrees();
+            let longitude = dir.z.atan2(dir.x).to_degrees();
+            writeln!(text, "{},{},{},{}", cell, latitude, longitude, value)?;
+        }
+        Ok(())
+    };
+    write().map_err(|error| error.to_string())?;
+    std::fs::write(path, text).map_err(|error| error.to_string())
+}
+
+// the cycle key shows the next layer on the globe, the export key writes the one being shown to the save folder
+pub fn layer_input(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    mut display: ResMut<DisplayLayer>,
+    columns: Res<Columns>,
+    grid: Res<Grid>,
+    mut toasts: EventWriter<ShowToast>,
+) {
+    if keyboard_input.just_pressed(settings.keys.cycle_layer) {
+        display.0 = display.0.next();
+        toasts.send(ShowToast::info(format!("Showing {}", display.0.name().replace('_', " "))));
+    }
+
+    if keyboard_input.just_pressed(settings.keys.export_layer) {
+        if !columns.fits(&grid) {
+            return;
+        }
+        let layer = display.0;
+        let path = settings.save_folder.join(format!("{}-{}.csv", timestamped_name(), layer.name()));
+        let values = layer.values(&columns);
+        let directions: Vec<Vec3> = (0..grid.cell_count()).map(|cell| grid.direction(cell)).collect();
+        ExportTask::spawn(&mut commands, move || {
+            write_layer_csv(&path, layer, &values, &directions).map(|_| format!("Exported {}", path.display()))
+        });
+    }
+}
@@ -0,0 +1,504 @@
+// Plate lifecycle
+//
+// The set of plates changes over a long run. Rifting tears a plate in two. Continents that have been pushed together
+// until the mountain belt between them is long and thick get welded into one plate along the suture. An oceanic plate
+// that has been pulled down under its neighbors until only a scrap is left is destroyed, and its neighbor takes the
+// scrap. Plate ids are indices into Plates and are never reused: a merged or destroyed plate stays behind with no
+// cells and no motion, so an id in the log always means the same plate.
+//
+// Every split, merge and destruction goes into the PlateEvents log along with when it happened. The log is saved
+// with the run and cut back on step back. The export key writes it to the save folder as CSV and JSON, for lining up
+// against supercontinent cycles, writing the files on the IO task pool.
+
+use std::{collections::HashMap, io::Write, path::Path};
+
+use bevy::prelude::*;
+
+use crate::{
+    boundaries::{BoundaryKind, PlateBoundaries},
+    clock::SimulationClock,
+    columns::{Columns, RockType},
+    config::WorldConfig,
+    grid::Grid,
+    plates::Plates,
+    saving::{timestamped_name, ExportTask},
+    settings::Settings,
+};
+
+// Settings for when plates weld together or disappear, part of WorldConfig
+#[derive(Reflect, Clone, Debug)]
+pub struct LifecycleSettings {
+    pub suturing: bool,
+
+    // two continental plates weld together once the collision between them runs this far along their boundary, in
+    // radians, and the crust along it has thickened to this on average
+    pub suture_length: f32,
+    pub suture_thickness: f32,
+
+    // share of the globe's surface an oceanic plate can shrink to before it is destroyed
+    pub min_plate_area: f32,
+}
+
+impl Default for LifecycleSettings {
+    fn default() -> Self {
+        Self {
+            suturing: true,
+            suture_length: 0.3,
+            suture_thickness: 0.3,
+            min_plate_area: 0.002,
+        }
+    }
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PlateEventKind
+{
+    Split,
+    Merge,
+    Destroy,
+}
+
+impl PlateEventKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            PlateEventKind::Split => "split",
+            PlateEventKind::Merge => "merge",
+            PlateEventKind::Destroy => "destroy",
+        }
+    }
+}
+
+#[derive(Reflect, Clone, Debug)]
+pub struct PlateEvent {
+    // clock at the start of the tick the event happened in
+    pub time_myr: f64,
+    pub tick: u64,
+
+    pub kind: PlateEventKind,
+
+    // for a split the plate that split and the new plate, for a merge the plate that is left and the one it took in,
+    // for a destruction the plate that is gone and the neighbor that took its last cells, if it had any
+    pub plate: u32,
+    pub other: Option<u32>,
+}
+
+// Every lifecycle event of the run so far, oldest first
+#[derive(Resource, Reflect, Clone, Default)]
+#[reflect(Resource)]
+pub struct PlateEvents {
+    pub events: Vec<PlateEvent>,
+}
+
+impl PlateEvents {
+    pub fn log(&mut self, clock: &SimulationClock, kind: PlateEventKind, plate: u32, other: Option<u32>) {
+        self.events.push(PlateEvent { time_myr: clock.time_myr, tick: clock.ticks, kind, plate, other });
+    }
+
+    // whether a plate has been merged into another one or destroyed
+    pub fn is_gone(&self, plate: u32) -> bool {
+        self.events.iter().any(|event| match event.kind {
+            PlateEventKind::Split => false,
+            PlateEventKind::Merge => event.other == Some(plate),
+            PlateEventKind::Destroy => event.plate == plate,
+        })
+    }
+
+    // forgets the events of ticks the clock has been stepped back past
+    pub fn rewind(&mut self, clock: &SimulationClock) {
+        self.events.retain(|event| event.tick < clock.ticks);
+    }
+}
+
+// cells of a plate to the left of a path across the globe, walking from its first point to its last
+// the points are joined by great circle arcs, and the path carries on along its first and last arcs past its ends,
+// so a path only has to cross a plate to cut it in two
+pub fn cells_left_of_path(grid: &Grid, columns: &Columns, plate: u32, path: &[Vec3]) -> Vec<usize> {
+    let normals: Vec<Vec3> =
+        path.windows(2).map(|ends| ends[0].cross(ends[1]).normalize_or_zero()).collect();
+    if normals.is_empty() {
+        return Vec::new();
+    }
+
+    (0..grid.cell_count())
+        .filter(|&cell| columns.plate_id[cell] == plate)
+        .filter(|&cell| {
+            //which side of the nearest arc the cell is on, at a corner of the path it goes by both arcs meeting there
+            let dir = grid.direction(cell);
+            let mut nearest = (f32::MAX, Vec3::ZERO);
+            for (i, normal) in normals.iter().enumerate() {
+                let (start, end) = (path[i], path[i + 1]);
+                let foot = dir.reject_from_normalized(*normal);
+                let candidates = [
+                    (start.cross(foot).dot(*normal) >= 0. && foot.cross(end).dot(*normal) >= 0.)
+                        .then(|| (dir.dot(*normal).abs().asin(), *normal)),
+                    Some((dir.angle_between(start), if i > 0 { normals[i - 1] + *normal } else { *normal })),
+                    Some((dir.angle_between(end), normals.get(i + 1).map_or(*normal, |next| *normal + *next))),
+                ];
+                for (distance, side) in candidates.into_iter().flatten() {
+                    if distance < nearest.0 {
+                        nearest = (distance, side);
+                    }
+                }
+            }
+            dir.dot(nearest.1) > 0.
+        })
+        .collect()
+}
+
+// moves some cells of a plate onto a new plate and logs the split, returns the new plate's id
+pub fn split_plate(
+    plates: &mut Plates,
+    columns: &mut Columns,
+    events: &mut PlateEvents,
+    clock: &SimulationClock,
+    plate: u32,
+    cells: &[usize],
+) -> u32 {
+    let new_plate = plates.split_off(columns, plate, cells);
+    events.log(clock, PlateEventKind::Split, plate, Some(new_plate));
+    new_plate
+}
+
+// welds one plate onto another, the two move on as one with the area weighted average of their rotations
+pub fn merge_plates(
+    grid: &Grid,
+    plates: &mut Plates,
+    columns: &mut Columns,
+    events: &mut PlateEvents,
+    clock: &SimulationClock,
+    keep: u32,
+    absorbed: u32,
+) {
+    let (mut keep_area, mut absorbed_area) = (0., 0.);
+    for cell in 0..grid.cell_count() {
+        if columns.plate_id[cell] == keep {
+            keep_area += grid.cell_area(cell);
+        } else if columns.plate_id[cell] == absorbed {
+            absorbed_area += grid.cell_area(cell);
+            columns.plate_id[cell] = keep;
+        }
+    }
+
+    let total = keep_area + absorbed_area;
+    if total > 0. {
+        let rotation = (plates.plates[keep as usize].rotation() * keep_area
+            + plates.plates[absorbed as usize].rotation() * absorbed_area)
+            / total;
+        plates.plates[keep as usize].set_rotation(rotation);
+    }
+    stop(plates, absorbed);
+    events.log(clock, PlateEventKind::Merge, keep, Some(absorbed));
+}
+
+// removes a plate, whatever is left of it goes to the neighboring plate it shares the longest boundary with
+pub fn destroy_plate(
+    grid: &Grid,
+    plates: &mut Plates,
+    columns: &mut Columns,
+    events: &mut PlateEvents,
+    clock: &SimulationClock,
+    plate: u32,
+) {
+    let cells: Vec<usize> = (0..grid.cell_count()).filter(|&cell| columns.plate_id[cell] == plate).collect();
+    let mut shared = HashMap::new();
+    for &cell in &cells {
+        for &neighbor in grid.neighbors(cell) {
+            let other = columns.plate_id[neighbor as usize];
+            if other != plate {
+                *shared.entry(other).or_insert(0) += 1;
+            }
+        }
+    }
+
+    //ties go to the lower id so the same run always picks the same neighbor
+    let heir = shared
+        .into_iter()
+        .max_by_key(|&(other, count)| (count, std::cmp::Reverse(other)))
+        .map(|(other, _)| other);
+    if let Some(heir) = heir {
+        for &cell in &cells {
+            columns.plate_id[cell] = heir;
+        }
+    }
+    stop(plates, plate);
+    events.log(clock, PlateEventKind::Destroy, plate, heir);
+}
+
+fn stop(plates: &mut Plates, plate: u32) {
+    let plate = &mut plates.plates[plate as usize];
+    plate.angular_velocity = 0.;
+    plate.pending_angle = 0.;
+}
+
+// welds continents that have collided for long enough and destroys oceanic plates that have all but vanished, run on
+// the fixed timestep after the collisions of the tick
+#[allow(clippy::too_many_arguments)]
+pub fn plate_lifecycle(
+    config: Res<WorldConfig>,
+    clock: Res<SimulationClock>,
+    grid: Res<Grid>,
+    mut columns: ResMut<Columns>,
+    mut plates: ResMut<Plates>,
+    mut boundaries: ResMut<PlateBoundaries>,
+    mut events: ResMut<PlateEvents>,
+) {
+    let settings = &config.lifecycle;
+    if !columns.fits(&grid) {
+        return;
+    }
+    let mut changed = false;
+
+    let plate_count = plates.plates.len();
+    let mut areas = vec![0f32; plate_count];
+    let mut continental = vec![false; plate_count];
+    let mut total_area = 0.;
+    for cell in 0..grid.cell_count() {
+        let area = grid.cell_area(cell);
+        total_area += area;
+        let plate = columns.plate_id[cell] as usize;
+        if plate < plate_count {
+            areas[plate] += area;
+            continental[plate] |= columns.rock_type[cell] == RockType::Granite;
+        }
+    }
+
+    if settings.suturing {
+        //length of continent on continent collision between each pair of plates, and the crust thickness along it
+        let mut collisions: HashMap<(u32, u32), (f32, f32, usize)> = HashMap::new();
+        for edge in boundaries.edges().iter().filter(|edge| edge.cell < edge.neighbor) {
+            let (cell, neighbor) = (edge.cell as usize, edge.neighbor as usize);
+            if edge.kind != BoundaryKind::Convergent
+                || columns.rock_type[cell] != RockType::Granite
+                || columns.rock_type[neighbor] != RockType::Granite
+            {
+                continue;
+            }
+            let pair = (edge.plate.min(edge.other_plate), edge.plate.max(edge.other_plate));
+            let collision = collisions.entry(pair).or_insert((0., 0., 0));
+            collision.0 += grid.direction(cell).angle_between(grid.direction(neighbor));
+            collision.1 += columns.crust_thickness[cell] + columns.crust_thickness[neighbor];
+            collision.2 += 2;
+        }
+
+        let mut pairs: Vec<_> = collisions.into_iter().collect();
+        pairs.sort_by_key(|(pair, _)| *pair);
+        //plates that have already merged this tick wait for the next one, their boundaries have changed
+        let mut merged = vec![false; plate_count];
+        for ((a, b), (length, thickness, cells)) in pairs {
+            if length < settings.suture_length || thickness / (cells as f32) < settings.suture_thickness {
+                continue;
+            }
+            if merged[a as usize] || merged[b as usize] {
+                continue;
+            }
+            //the smaller plate is taken into the larger one
+            let (keep, absorbed) = if areas[a as usize] >= areas[b as usize] { (a, b) } else { (b, a) };
+            merge_plates(&grid, &mut plates, &mut columns, &mut events, &clock, keep, absorbed);
+            areas[keep as usize] += areas[absorbed as usize];
+            areas[absorbed as usize] = 0.;
+            merged[a as usize] = true;
+            merged[b as usize] = true;
+            changed = true;
+        }
+    }
+
+    for plate in 0..plate_count as u32 {
+        let shrunk = areas[plate as usize] < settings.min_plate_area * total_area && !continental[plate as usize];
+        if shrunk && !events.is_gone(plate) {
+            destroy_plate(&grid, &mut plates, &mut columns, &mut events, &clock, plate);
+            changed = true;
+        }
+    }
+
+    if changed {
+        boundaries.classify(&grid, &columns, &plates);
+    }
+}
+
+// starts the event log of a new run empty
+pub fn plate_events_setup(mut events: ResMut<PlateEvents>) {
+    events.events.clear();
+}
+
+fn write_events_csv(path: &Path, events: &[PlateEvent]) -> Result<(), String> {
+    let mut text = Vec::new();
+    let mut write = || -> std::io::Result<()> {
+        writeln!(text, "time_myr,tick,event,plate,other")?;
+        for event in events {
+            let other = event.other.map(|other| other.to_string()).unwrap_or_default();
+            writeln!(text, "{},{},{},{},{}", event.time_myr, event.tick, event.kind.name(), event.plate, other)?;
+        }
+        Ok(())
+    };
+    write().map_err(|error| error.to_string())?;
+    std::fs::write(path, text).map_err(|error| error.to_string())
+}
+
+fn write_events_json(path: &Path, events: &[PlateEvent]) -> Result<(), String> {
+    let json: Vec<serde_json::Value> = events
+        .iter()
+        .map(|event| {
+            serde_json::json!({
+                "time_myr": event.time_myr,
+                "tick": event.tick,
+                "event": event.kind.name(),
+                "plate": event.plate,
+                "other": event.other,
+            })
+        })
+        .collect();
+    let text = serde_json::to_vec_pretty(&json).map_err(|error| error.to_string())?;
+    std::fs::write(path, text).map_err(|error| error.to_string())
+}
+
+// the export key writes the event log to the save folder, once as CSV and once as JSON
+pub fn plate_events_input(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    events: Res<PlateEvents>,
+) {
+    if !keyboard_input.just_pressed(settings.keys.export_events) {
+        return;
+    }
+
+    let base = settings.save_folder.join(format!("{}-plate-events", timestamped_name()));
+    let (csv, json) = (base.with_extension("csv"), base.with_extension("json"));
+    let events = events.events.clone();
+    ExportTask::spawn(&mut commands, move || {
+        write_events_csv(&csv, &events)
+            .and_then(|_| write_events_json(&json, &events))
+            .map(|_| format!("Exported {} plate events to {}", events.len(), csv.display()))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::{grid::GridKind, plates::Plate};
+
+    // three oceanic plates: 0 east of the meridian x = 0, 1 west of it, and 2 a small cap around +y cut out of both
+    fn three_plates() -> (Grid, Plates, Columns) {
+        let config = WorldConfig { grid: GridKind::Icosahedral, ico_subdivisions: 3, ..default() };
+        let grid = Grid::from_config(&config);
+        let mut columns = Columns::new(grid.cell_count(), RockType::Basalt, &config);
+        for cell in 0..grid.cell_count() {
+            let dir = grid.direction(cell);
+            columns.plate_id[cell] = if dir.y > 0.95 {
+                2
+            } else if dir.x > 0. {
+                0
+            } else {
+                1
+            };
+        }
+        let plates = (0..3)
+            .map(|id| {
+                let pole = Vec3::new(1., id as f32, 0.5).normalize();
+                Plate { id, pole, angular_velocity: 0.01, ..default() }
+            })
+            .collect();
+        (grid, Plates { plates }, columns)
+    }
+
+    fn cells_of(columns: &Columns, plate: u32) -> Vec<usize> {
+        (0..columns.cell_count()).filter(|&cell| columns.plate_id[cell] == plate).collect()
+    }
+
+    // every plate sits at its own id, every cell belongs to a plate that exists, and plates that are gone have no cells
+    fn assert_consistent(plates: &Plates, columns: &Columns, events: &PlateEvents) {
+        for (index, plate) in plates.plates.iter().enumerate() {
+            assert_eq!(plate.id as usize, index);
+            if events.is_gone(plate.id) {
+                assert!(cells_of(columns, plate.id).is_empty(), "plate {} is gone but has cells", plate.id);
+                assert_eq!(plate.angular_velocity, 0.);
+            }
+        }
+        assert!(columns.plate_id.iter().all(|&plate| (plate as usize) < plates.plates.len()));
+    }
+
+    #[test]
+    fn split_merge_and_destroy_keep_plates_consistent() {
+        let (grid, mut plates, mut columns) = three_plates();
+        let mut events = PlateEvents::default();
+        let mut clock = SimulationClock::default();
+        let cell_count = grid.cell_count();
+
+        //split the southern half off plate 0
+        clock.ticks = 5;
+        let south: Vec<usize> = cells_of(&columns, 0).into_iter().filter(|&cell| grid.direction(cell).y < 0.).collect();
+        let kept = cells_of(&columns, 0).len() - south.len();
+        let new_plate = split_plate(&mut plates, &mut columns, &mut events, &clock, 0, &south);
+        assert_eq!(new_plate, 3);
+        assert_eq!(cells_of(&columns, 3), south);
+        assert_eq!(cells_of(&columns, 0).len(), kept);
+        assert_eq!(plates.plates[3].rotation(), plates.plates[0].rotation());
+        assert_consistent(&plates, &columns, &events);
+
+        //weld it back on
+        clock.ticks = 8;
+        merge_plates(&grid, &mut plates, &mut columns, &mut events, &clock, 0, 3);
+        assert!(cells_of(&columns, 3).is_empty());
+        assert_eq!(cells_of(&columns, 0).len(), kept + south.len());
+        assert_consistent(&plates, &columns, &events);
+
+        //the cap goes to whichever of the big plates it shares more boundary with
+        clock.ticks = 13;
+        let cap = cells_of(&columns, 2);
+        destroy_plate(&grid, &mut plates, &mut columns, &mut events, &clock, 2);
+        let heir = columns.plate_id[cap[0]];
+        assert!(heir == 0 || heir == 1);
+        assert!(cap.iter().all(|&cell| columns.plate_id[cell] == heir));
+        assert_consistent(&plates, &columns, &events);
+        assert_eq!(cells_of(&columns, 0).len() + cells_of(&columns, 1).len(), cell_count);
+
+        //ids are never reused, the next split gets a new one
+        clock.ticks = 21;
+        let west = cells_of(&columns, 1).into_iter().filter(|&cell| grid.direction(cell).z > 0.).collect::<Vec<_>>();
+        assert_eq!(split_plate(&mut plates, &mut columns, &mut events, &clock, 1, &west), 4);
+        assert_consistent(&plates, &columns, &events);
+
+        let logged: Vec<(u64, PlateEventKind, u32, Option<u32>)> =
+            events.events.iter().map(|event| (event.tick, event.kind, event.plate, event.other)).collect();
+        assert_eq!(
+            logged,
+            [
+                (5, PlateEventKind::Split, 0, Some(3)),
+                (8, PlateEventKind::Merge, 0, Some(3)),
+                (13, PlateEventKind::Destroy, 2, Some(heir)),
+                (21, PlateEventKind::Split, 1, Some(4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn vanishing_ocean_plate_is_destroyed_and_logged() {
+        let (grid, plates, columns) = three_plates();
+        let cap_area: f32 = cells_of(&columns, 2).iter().map(|&cell| grid.cell_area(cell)).sum();
+        let mut config = WorldConfig { grid: GridKind::Icosahedral, ico_subdivisions: 3, ..default() };
+        config.lifecycle.min_plate_area = 2. * cap_area / (4. * std::f32::consts::PI);
+
+        let mut world = World::new();
+        world.insert_resource(config);
+        world.insert_resource(grid);
+        world.insert_resource(columns);
+        world.insert_resource(plates);
+        world.init_resource::<SimulationClock>();
+        world.init_resource::<PlateBoundaries>();
+        world.init_resource::<PlateEvents>();
+        world.run_system_once(plate_lifecycle);
+
+        let (plates, columns, events) =
+            (world.resource::<Plates>(), world.resource::<Columns>(), world.resource::<PlateEvents>());
+        assert_eq!(events.events.len(), 1);
+        assert_eq!((events.events[0].kind, events.events[0].plate), (PlateEventKind::Destroy, 2));
+        assert_consistent(plates, columns, events);
+
+        //and the boundaries were found again without it
+        let boundaries = world.resource::<PlateBoundaries>();
+        assert!(!boundaries.edges().is_empty());
+        assert!(boundaries.edges().iter().all(|edge| edge.plate != 2 && edge.other_plate != 2));
+    }
+}
@@ -0,0 +1,500 @@
+// Loading simulations
+//
+// The Load Simulation button opens a file browser drawn with Bevy UI. The save folder is read on the IO task pool,
+// and every save is opened there to pull out its geologic time, seed, grid size and a small map of its heights.
+// Picking a save applies it through SavePipeline and starts the simulation from the restored state.
+
+use std::path::{Path, PathBuf};
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+    tasks::{block_on, poll_once, IoTaskPool, Task},
+};
+use bevy_save::prelude::*;
+
+use crate::{
+    boundaries::BoundaryOverlay,
+    folder_picker::FolderPicker,
+    grid::{Grid, GridKind},
+    layers::height_color,
+    save_file::{is_save_file, read_save},
+    settings::Settings,
+    toast::ShowToast,
+    AppState, RunOrigin, SavePipeline, Shape,
+};
+
+// size of the map drawn for every save, in pixels
+const THUMBNAIL_WIDTH: u32 = 96;
+const THUMBNAIL_HEIGHT: u32 = 48;
+
+// only the newest saves are listed, the panel has no scrolling
+const MAX_LISTED: usize = 8;
+
+// Sent by the Load Simulation button
+#[derive(Event)]
+pub struct OpenFileBrowser;
+
+// Sent when a save is picked in the file browser
+#[derive(Event)]
+pub struct LoadRequest(pub PathBuf);
+
+// What is known about a save without loading it
+pub struct SaveInfo {
+    path: PathBuf,
+    time_myr: f64,
+    seed: u64,
+
+    // grid size as shown in the list, rows x cols or the number of subdivisions
+    grid: String,
+
+    // THUMBNAIL_WIDTH x THUMBNAIL_HEIGHT rgba pixels, empty if the save has no heights
+    thumbnail: Vec<u8>,
+}
+
+// Marks the root node of the file browser
+#[derive(Component)]
+pub struct FileBrowser;
+
+// Marks the node the save entries get added to
+#[derive(Component)]
+pub struct BrowserList;
+
+// Marks the text telling the user what the browser is doing
+#[derive(Component)]
+pub struct BrowserStatus;
+
+#[derive(Component)]
+pub enum BrowserAction {
+    Open(PathBuf),
+    Close,
+}
+
+// the save folder being read in the background, lives on the file browser entity
+#[derive(Component)]
+pub struct SaveScan(Task<Result<Vec<SaveInfo>, String>>);
+
+// draws an equirectangular map of the heights, blue below sea level and green to brown above it
+fn thumbnail_pixels(heights: &[f32], sea_level: f32, grid: &Grid) -> Vec<u8> {
+    if heights.len() != grid.cell_count() {
+        return Vec::new();
+    }
+
+    let mut pixels = Vec::with_capacity((THUMBNAIL_WIDTH * THUMBNAIL_HEIGHT * 4) as usize);
+    for y in 0..THUMBNAIL_HEIGHT {
+        let latitude = std::f32::consts::FRAC_PI_2 - std::f32::consts::PI * (y as f32 + 0.5) / THUMBNAIL_HEIGHT as f32;
+        for x in 0..THUMBNAIL_WIDTH {
+            let longitude = 2. * std::f32::consts::PI * (x as f32 + 0.5) / THUMBNAIL_WIDTH as f32;
+            let dir = Vec3::new(
+                latitude.cos() * longitude.cos(),
+                latitude.sin(),
+                latitude.cos() * longitude.sin(),
+            );
+            let color = height_color(heights[grid.cell_from_direction(dir)], sea_level);
+            pixels.extend_from_slice(&color.as_rgba_u8());
+        }
+    }
+    pixels
+}
+
+// opens one save and reads what the browser shows about it, runs on the IO task pool
+fn read_save_info(path: &Path) -> Result<SaveInfo, String> {
+    let save = read_save(path)?;
+
+    Ok(SaveInfo {
+        path: path.to_path_buf(),
+        time_myr: save.clock.time_myr,
+        seed: save.config.seed,
+        grid: match save.config.grid {
+            GridKind::LatLong => format!("{}x{}", save.config.rows, save.config.cols),
+            GridKind::Icosahedral => format!("ico {}", save.config.ico_subdivisions),
+            GridKind::CubeSphere => format!("cube {}", save.config.cube_face_cells),
+        },
+        thumbnail: thumbnail_pixels(&save.columns.heights(), save.config.erosion.sea_level, &Grid::from_config(&save.config)),
+    })
+}
+
+// lists the saves in a folder, newest first, skipping files that can not be read
+fn scan_folder(folder: &Path) -> Result<Vec<SaveInfo>, String> {
+    let entries = std::fs::read_dir(folder).map_err(|error| error.to_string())?;
+
+    let mut saves: Vec<(std::time::SystemTime, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && is_save_file(path))
+        .map(|path| {
+            let modified = std::fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or(std::time::UNIX_EPOCH);
+            (modified, path)
+        })
+        .collect();
+    saves.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+
+    Ok(saves
+        .iter()
+        .take(MAX_LISTED)
+        .filter_map(|(_, path)| read_save_info(path).ok())
+        .collect())
+}
+
+// spawns the file browser panel and starts reading the save folder
+#[allow(clippy::type_complexity)]
+pub fn open_file_browser(
+    mut commands: Commands,
+    mut events: EventReader<OpenFileBrowser>,
+    panel_query: Query<Entity, Or<(With<FileBrowser>, With<FolderPicker>)>>,
+    settings: Res<Settings>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+
+    //opening it again refreshes the list, the folder picker sits in the same spot
+    for entity in &panel_query {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let folder = settings.save_folder.clone();
+    let scan_folder_path = folder.clone();
+    let task = IoTaskPool::get().spawn(async move { scan_folder(&scan_folder_path) });
+
+    commands.spawn
+    (
+        (
+            // Create the panel the saves are listed in
+            NodeBundle
+            {
+                style: Style
+                {
+                    // Place the panel over the menu
+                    position_type: PositionType::Absolute,
+
+                    // Set the gap between the panel and the top right of the window
+                    top: Val::Px(120.0),
+                    right: Val::Px(20.0),
+
+                    // Set the width of the panel in pixels
+                    width: Val::Px(520.0),
+
+                    // List everything in a column
+                    flex_direction: FlexDirection::Column,
+
+                    // Add a 10 pixel gap between entries
+                    row_gap: Val::Px(10.0),
+
+                    // Leave some room around the contents
+                    padding: UiRect::all(Val::Px(10.0)),
+
+                    ..default()
+                },
+
+                // Darken the area behind the panel so it is readable over the globe
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+
+                ..default()
+            },
+
+            FileBrowser,
+            SaveScan(task),
+        )
+    )
+
+    .with_children
+    (
+        |parent|
+        {
+            // Title of the panel
+            parent.spawn
+            (
+                TextBundle::from_section
+                (
+                    "Load Simulation",
+                    TextStyle
+                    {
+                        font: default(),
+                        font_size: 40.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                )
+            );
+
+            // The folder being listed
+            parent.spawn
+            (
+                TextBundle::from_section
+                (
+                    folder.display().to_string(),
+                    TextStyle
+                    {
+                        font: default(),
+                        font_size: 16.0,
+                        color: Color::rgb(0.6, 0.6, 0.6),
+                    },
+                )
+            );
+
+            // Tells the user the folder is being read, replaced once the saves are listed
+            parent.spawn
+            (
+                (
+                    TextBundle::from_section
+                    (
+                        "Looking for saves...",
+                        TextStyle
+                        {
+                            font: default(),
+                            font_size: 20.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                    ),
+                    BrowserStatus,
+                )
+            );
+
+            // The saves get added in here by list_saves
+            parent.spawn
+            (
+                (
+                    NodeBundle
+                    {
+                        style: Style
+                        {
+                            flex_direction: FlexDirection::Column,
+                            row_gap: Val::Px(6.0),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    BrowserList,
+                )
+            );
+
+            // Closes the browser without loading anything
+            parent.spawn
+            (
+                (
+                    ButtonBundle
+                    {
+                        style: Style
+                        {
+                            width: Val::Px(150.0),
+                            height: Val::Px(45.0),
+                            border: UiRect::all(Val::Px(3.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        border_color: BorderColor(Color::WHITE),
+                        background_color: Color::rgb(0.15, 0.15, 0.15).into(),
+                        ..default()
+                    },
+                    BrowserAction::Close,
+                )
+            )
+
+            .with_children
+            (
+                |parent|
+                {
+                    parent.spawn
+                    (
+                        TextBundle::from_section
+                        (
+                            "Close",
+                            TextStyle
+                            {
+                                font: default(),
+                                font_size: 24.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        )
+                    );
+                }
+            );
+        }
+    );
+}
+
+// waits for the folder scan and adds a button for every save it found
+pub fn list_saves(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut scan_query: Query<(Entity, &mut SaveScan)>,
+    list_query: Query<Entity, With<BrowserList>>,
+    mut status_query: Query<&mut Text, With<BrowserStatus>>,
+) {
+    for (entity, mut scan) in &mut scan_query {
+        let Some(result) = block_on(poll_once(&mut scan.0)) else {
+            continue;
+        };
+        commands.entity(entity).remove::<SaveScan>();
+
+        let status = match &result {
+            Ok(saves) if saves.is_empty() => "No saves in this folder".to_string(),
+            Ok(_) => "Pick a save to load".to_string(),
+            Err(error) => format!("Could not read the folder: {}", error),
+        };
+        for mut text in &mut status_query {
+            text.sections[0].value = status.clone();
+        }
+
+        let (Ok(saves), Ok(list)) = (result, list_query.get_single()) else {
+            continue;
+        };
+
+        for save in saves {
+            let name = save.path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+            let details = format!("{:.0} Myr   seed {}   {}", save.time_myr, save.seed, save.grid);
+
+            let thumbnail = if save.thumbnail.is_empty() {
+                None
+            } else {
+                Some(images.add(Image::new(
+                    Extent3d { width: THUMBNAIL_WIDTH, height: THUMBNAIL_HEIGHT, depth_or_array_layers: 1 },
+                    TextureDimension::D2,
+                    save.thumbnail,
+                    TextureFormat::Rgba8UnormSrgb,
+                    RenderAssetUsages::RENDER_WORLD,
+                )))
+            };
+
+            commands.entity(list).with_children
+            (
+                |parent|
+                {
+                    parent.spawn
+                    (
+                        (
+                            ButtonBundle
+                            {
+                                style: Style
+                                {
+                                    width: Val::Percent(100.0),
+                                    border: UiRect::all(Val::Px(3.0)),
+                                    padding: UiRect::all(Val::Px(4.0)),
+                                    align_items: AlignItems::Center,
+                                    column_gap: Val::Px(10.0),
+                                    ..default()
+                                },
+                                border_color: BorderColor(Color::WHITE),
+                                background_color: Color::rgb(0.15, 0.15, 0.15).into(),
+                                ..default()
+                            },
+                            BrowserAction::Open(save.path),
+                        )
+                    )
+
+                    .with_children
+                    (
+                        |parent|
+                        {
+                            // Map of the save's heights
+                            if let Some(thumbnail) = thumbnail
+                            {
+                                parent.spawn
+                                (
+                                    ImageBundle
+                                    {
+                                        style: Style
+                                        {
+                                            width: Val::Px(THUMBNAIL_WIDTH as f32),
+                                            height: Val::Px(THUMBNAIL_HEIGHT as f32),
+                                            ..default()
+                                        },
+                                        image: UiImage::new(thumbnail),
+                                        ..default()
+                                    }
+                                );
+                            }
+
+                            // Name of the save with its time, seed and grid size below it
+                            parent.spawn
+                            (
+                                TextBundle::from_sections
+                                ([
+                                    TextSection::new
+                                    (
+                                        format!("{}\n", name),
+                                        TextStyle
+                                        {
+                                            font: default(),
+                                            font_size: 22.0,
+                                            color: Color::rgb(0.9, 0.9, 0.9),
+                                        },
+                                    ),
+                                    TextSection::new
+                                    (
+                                        details,
+                                        TextStyle
+                                        {
+                                            font: default(),
+                                            font_size: 18.0,
+                                            color: Color::rgb(0.6, 0.6, 0.6),
+                                        },
+                                    ),
+                                ])
+                            );
+                        }
+                    );
+                }
+            );
+        }
+    }
+}
+
+// handles the save and close buttons of the file browser
+#[allow(clippy::type_complexity)]
+pub fn browser_button_system(
+    mut commands: Commands,
+    mut interaction_query: Query<(&Interaction, &BrowserAction, &mut BorderColor), (Changed<Interaction>, With<Button>)>,
+    browser_query: Query<Entity, With<FileBrowser>>,
+    mut load_requests: EventWriter<LoadRequest>,
+) {
+    for (interaction, action, mut border_color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                if let BrowserAction::Open(path) = action {
+                    load_requests.send(LoadRequest(path.clone()));
+                }
+                for entity in &browser_query {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+            Interaction::Hovered => border_color.0 = Color::LIME_GREEN,
+            Interaction::None => border_color.0 = Color::WHITE,
+        }
+    }
+}
+
+// applies a picked save and starts the simulation from it
+// the menu is only torn down once the save has been read, so a broken file leaves the user on the menu
+pub fn load_save(world: &mut World) {
+    let Some(LoadRequest(path)) = world.resource_mut::<Events<LoadRequest>>().drain().last() else {
+        return;
+    };
+
+    let registry = world.resource::<AppTypeRegistry>().clone();
+    let loaded = read_save(&path)
+        .and_then(|save| SavePipeline::apply(world, &save.into_snapshot(registry.clone())).map_err(|error| error.to_string()));
+    if let Err(error) = loaded {
+        world.send_event(ShowToast::error(format!("Could not load {}: {}", path.display(), error)));
+        return;
+    }
+
+    // Delete the menu globe, buttons and labels, render_setup builds the globe again from the loaded heights
+    let entities: Vec<Entity> = world
+        .query_filtered::<Entity, Or<(With<Style>, With<Shape>, With<BoundaryOverlay>)>>()
+        .iter(world)
+        .collect();
+    for entity in entities {
+        world.despawn(entity);
+    }
+
+    world.insert_resource(RunOrigin::Loaded);
+    world.resource_mut::<NextState<AppState>>().set(AppState::Simulate);
+    world.send_event(ShowToast::info(format!("Loaded {}", path.display())));
+}
@@ -1,104 +1,167 @@
 // Libraries/Crates/Packages
 use bevy::
 {
-    app::AppExit, math::quat, prelude::*, render::
+    app::AppExit, prelude::*, render::
     {
-        camera::{self, RenderTarget}, mesh::{Indices, VertexAttributeValues}, render_asset::RenderAssetUsages, render_resource::{Extent3d, PrimitiveTopology, TextureDimension, TextureFormat}
-    }, window::WindowRef
+        camera::RenderTarget, mesh::Indices, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology
+    }
 };
 
+use bevy::ecs::schedule::SystemConfigs;
 use bevy_save::prelude::*;
 
+mod boundaries;
+mod clock;
+mod columns;
+mod config;
+mod cube;
+mod erosion;
+mod flexure;
+mod forces;
+mod folder_picker;
+mod grid;
+mod history;
+mod hotspots;
+mod ico;
+mod isostasy;
+mod layers;
+mod lifecycle;
+mod loading;
+mod orogeny;
+mod plates;
+mod save_file;
+mod saving;
+mod settings;
+mod spreading;
+mod terrain;
+mod thermal;
+mod toast;
+
+use boundaries::{boundary_input, classify_boundaries, draw_boundaries, empty_overlay_mesh, BoundaryOverlay, PlateBoundaries};
+use clock::{
+    advance_clock, clock_input, clock_setup, simulation_running, sync_fixed_timestep, update_clock_text, ClockText, PauseText,
+    SimulationClock, SimulationSet, PAUSE_GLYPH,
+};
+use columns::{Columns, RockType};
+use config::WorldConfig;
+use erosion::{erode, erosion_setup, ErosionSettings, ErosionState};
+use flexure::{flex_lithosphere, flexure_setup, Flexure, FlexureSettings};
+use forces::{drive_plates, PlateForceSettings};
+use folder_picker::{folder_button_system, open_folder_picker, OpenFolderPicker};
+use grid::{grid_setup, Grid, GridKind};
+use history::{history_input, history_setup, record_checkpoint, step_back, SimulationHistory, StepBack};
+use hotspots::{draw_hotspots, hotspot_volcanism, hotspots_setup, place_hotspot, rewind_hotspots, Hotspot, HotspotSettings};
+use isostasy::{isostasy, isostasy_setup, IsostasyModel, IsostasySettings};
+use layers::{layer_colors, layer_input, DisplayLayer};
+use lifecycle::{plate_events_input, plate_events_setup, plate_lifecycle, LifecycleSettings, PlateEvent, PlateEventKind, PlateEvents};
+use loading::{browser_button_system, list_saves, load_save, open_file_browser, LoadRequest, OpenFileBrowser};
+use orogeny::{orogeny, OrogenySettings};
+use plates::{advance_plates, plates_setup, Plate, PlateGenSettings, Plates};
+use saving::{finish_exports, finish_saves, start_save, SaveRequest};
+use settings::{settings_setup, track_settings, write_changed_settings, write_settings_on_exit, Keybindings, Settings};
+use spreading::{cool_ocean_floor, rift_continents, SpreadingSettings};
+use terrain::{terrain_setup, TerrainSettings};
+use thermal::{thermal_erode, TalusSettings, ThermalSettings};
+use toast::{expire_toasts, spawn_toasts, ShowToast};
+
 fn main()
 {
-    //subdivided triangle coordinate reference - largely unused for now, but in future will likely be used to lookup height values when generating mesh
-    //
-    //           c
-    //          /\
-    //         /__\
-    //        /\  /\
-    //       /__\/__\
-    //      /\  /\  /\
-    //     /__\/__\/__\
-    //    /\  /\  /\  /\
-    //   /__\/__\/__\/__\
-    //  b                a
-    //
-    //
-    //
-    //  b________________a
-    //   \  /\  /\  /\  /
-    //    \/__\/__\/__\/
-    //     \  /\  /\  /
-    //      \/__\/__\/
-    //       \  /\  /
-    //        \/__\/
-    //         \  /
-    //          \/
-    //           c
-    //
-    //
-    //
-    //because it is 2d, we can track each point on a supertriangle uniquely with only 2 coordinates 
-    //a is distance from left edge, b is distance from right edge, c won't be stored, but represents dist from the horizontal edge and can be calculated as 2^subdivisions - a - b
-    //if any of the coords are negative, we are on a different supertri.
-    //if any of the coords are 0, we will have an edge (haha get it) case that we need to figure out because two (on edge) or five (on corner) supertris share this point
-    //the more i think about this the more i think it should be ok, because it will only ever be a problem if the same point on two supertris has two different values, and we should be able to prevent that
-    //
-    //
-    //
-    //icosahedron net array reference
-    //                                 /\  /\  /\  /\  /\   [0,0]-[0,4]
-    //      /\  /\  /\  /\  /\        /__\/__\/__\/__\/__\
-    //     /__\/__\/__\/__\/__\       \  /\  /\  /\  /\  /
-    //    /\  /\  /\  /\  /\  /        \/  \/  \/  \/  \/   [1,0]-[1,4]
-    //   /__\/__\/__\/__\/__\/  ==>  /\  /\  /\  /\  /\     [2,0]-[2,4]
-    //   \  /\  /\  /\  /\  /       /__\/__\/__\/__\/__\
-    //    \/  \/  \/  \/  \/        \  /\  /\  /\  /\  /
-    //                               \/  \/  \/  \/  \/     [3,0]-[3,4]
-    //
-    //
-    //coords of adjacent supertri to super tri with index [i,j] by i (aka row):
-    // i  left          right         vertical
-    // 0: [0, (j-1)%5], [0, (j+1)%5], [1, j]
-    // 1: [2, j],       [2, (j+1)%5], [0, j]
-    // 2: [1, (j-1)%5], [1, j],       [3, j]
-    // 3: [3, (j-1)%5], [3, (j+1)%5], [2, j]
-    //
-    //
-    // phi = (1 + sqrt(5))/2)
-    // d = sqrt(1 + phi^2) = sqrt( 2 * ( 5 + sqrt(5)))/2 ~= 1.902113
-    //
-    //
-    // coords of top/bottom point: (0, d, 0), (0, -d, 0)
-    //
-    // coords of top ring: (0, 0.85065, -1.7013),  (phi, 0.85065, -sqrt(d^2 - phi^2 - 0.85065^2), (1, 0.85065, sqrt(d^2 - 1 - 0.85065^2)), (-1, 0.85065, sqrt(d^2 - 1 - 0.85065^2)), (-phi, 0.85065, -sqrt(d^2 - phi^2 - 0.85065^2))
-    //
-    // coords of bottom ring: (-1, -0.85065, -sqrt(d^2 - 1 - 0.85065^2)), (1, -0.85065, -sqrt(d^2 - 1 - 0.85065^2)), (phi, -0.85065, sqrt(d^2 - phi^2 - 0.85065^2)), (0, -0.85065, 1.7013), (-phi, -0.85065, sqrt(d^2 - phi^2 - 0.85065^2))
-
-    //let mut 
-    
-    let h = HeightValues { values: Vec::<Vec<f32>>::new() };
+    //the icosahedral grid's supertriangle and net references live in ico.rs
 
     // Create the main menu app
     let mut app = App::new();
 
-    // Insert the heights into the app
-    app.insert_resource(h);
+    // Insert the per-cell columns, the plates they ride on, and the config used to generate them
+    app.init_resource::<Columns>()
+        .init_resource::<Plates>()
+        .init_resource::<ErosionState>()
+        .init_resource::<DisplayLayer>()
+        .init_resource::<PlateBoundaries>()
+        .init_resource::<PlateEvents>()
+        .init_resource::<Flexure>()
+        .init_resource::<WorldConfig>()
+        .init_resource::<Grid>()
+        .init_resource::<SimulationClock>()
+        .init_resource::<SimulationHistory>()
+        .init_resource::<Settings>()
+        .init_resource::<RunOrigin>()
+        .add_event::<StepBack>()
+        .add_event::<SaveRequest>()
+        .add_event::<OpenFileBrowser>()
+        .add_event::<OpenFolderPicker>()
+        .add_event::<LoadRequest>()
+        .add_event::<ShowToast>();
+
+    // Register the types that get written into saves
+    app.register_type::<WorldConfig>()
+        .register_type::<SimulationClock>()
+        .register_type::<PlateGenSettings>()
+        .register_type::<TerrainSettings>()
+        .register_type::<ErosionSettings>()
+        .register_type::<ThermalSettings>()
+        .register_type::<OrogenySettings>()
+        .register_type::<SpreadingSettings>()
+        .register_type::<LifecycleSettings>()
+        .register_type::<IsostasySettings>()
+        .register_type::<IsostasyModel>()
+        .register_type::<FlexureSettings>()
+        .register_type::<HotspotSettings>()
+        .register_type::<PlateForceSettings>()
+        .register_type::<Hotspot>()
+        .register_type::<TalusSettings>()
+        .register_type::<GridKind>()
+        .register_type::<Columns>()
+        .register_type::<RockType>()
+        .register_type::<Vec<f32>>()
+        .register_type::<Vec<u32>>()
+        .register_type::<Vec<RockType>>()
+        .register_type::<Plates>()
+        .register_type::<Plate>()
+        .register_type::<Vec<Plate>>()
+        .register_type::<PlateEvents>()
+        .register_type::<PlateEvent>()
+        .register_type::<PlateEventKind>()
+        .register_type::<Vec<PlateEvent>>()
+        .register_type::<Option<u32>>();
+
+    // Register the types that get written into the settings file
+    app.register_type::<Settings>()
+        .register_type::<Keybindings>()
+        .register_type::<Option<u64>>()
+        .register_type::<std::path::PathBuf>();
 
     // Add systems to the main app
-    app.add_plugins(DefaultPlugins)
+    app.add_plugins((DefaultPlugins, SavePlugins))
         .init_state::<AppState>()
-        .add_systems(Startup, camera_setup)
-        .add_systems(OnEnter(AppState::MainMenu), (menu_setup, render_setup))
+        .add_systems(Startup, (camera_setup, settings_setup))
+        .add_systems(OnEnter(AppState::MainMenu), (menu_setup, grid_setup, render_setup.after(grid_setup), terrain_setup.after(render_setup), classify_boundaries.after(render_setup)))
         .add_systems(Update, (main_button_system.run_if(in_state(AppState::MainMenu)), input_handler.run_if(in_state(AppState::MainMenu))))
-        .add_systems(OnEnter(AppState::Simulate), (simulate_gui, render_setup))
-        .add_systems(Update, (simulate_button_system.run_if(in_state(AppState::Simulate)), input_handler.run_if(in_state(AppState::Simulate))));
+        .add_systems(Update, (open_file_browser, list_saves, browser_button_system, load_save).chain().run_if(in_state(AppState::MainMenu)))
+        .add_systems(Update, (open_folder_picker, folder_button_system).chain().run_if(in_state(AppState::MainMenu)))
+        .add_systems(Update, (track_settings, write_changed_settings.run_if(resource_changed::<Settings>)).chain())
+        .add_systems(Last, write_settings_on_exit)
+        .add_systems(OnEnter(AppState::Simulate), (simulate_gui, grid_setup, render_setup.after(grid_setup), erosion_setup, flexure_setup, history_setup.after(isostasy_setup).after(clock_setup), classify_boundaries.after(render_setup).after(plates_setup)))
+        .add_systems(OnEnter(AppState::Simulate), (clock_setup, plates_setup.after(render_setup), terrain_setup.after(plates_setup), isostasy_setup.after(terrain_setup), plate_events_setup, hotspots_setup.after(clock_setup)).run_if(resource_equals(RunOrigin::New)))
+        .add_systems(Update, (simulate_button_system.run_if(in_state(AppState::Simulate)), input_handler.run_if(in_state(AppState::Simulate))))
+        .add_systems(Update, (clock_input, sync_fixed_timestep.run_if(resource_changed::<SimulationClock>), update_clock_text).chain().run_if(in_state(AppState::Simulate)))
+        .add_systems(Update, (history_input, step_back, classify_boundaries.run_if(on_event::<StepBack>()), rewind_hotspots.run_if(on_event::<StepBack>())).chain().run_if(in_state(AppState::Simulate)))
+        .add_systems(Update, (layer_input, boundary_input, plate_events_input, place_hotspot, draw_hotspots).run_if(in_state(AppState::Simulate)))
+        .add_systems(Update, (start_save, finish_saves, finish_exports, spawn_toasts, expire_toasts).chain())
+        .configure_sets(FixedUpdate, SimulationSet.run_if(in_state(AppState::Simulate)).run_if(simulation_running))
+        .add_systems(FixedUpdate, simulation_tick().in_set(SimulationSet))
+        .add_systems(Update, refresh_globe_mesh.after(input_handler).after(layer_input).run_if(resource_exists_and_changed::<Columns>.or_else(resource_changed::<DisplayLayer>)))
+        .add_systems(Update, draw_boundaries.run_if(resource_changed::<PlateBoundaries>));
 
     // Run the main app
     app.run();
 }
 
+// Every system of one simulation tick, in the order they run
+fn simulation_tick() -> SystemConfigs
+{
+    (rift_continents, advance_plates, cool_ocean_floor, classify_boundaries, orogeny, hotspot_volcanism, plate_lifecycle, drive_plates, erode, thermal_erode, flex_lithosphere, isostasy, advance_clock, record_checkpoint).chain()
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, States)]
 enum AppState
 {
@@ -107,6 +170,16 @@ enum AppState
     Simulate,
 }
 
+// Whether the simulation being entered starts from a new world or from a loaded save
+// a loaded save already has its plates, terrain and clock, so the generators are skipped
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug, Default)]
+enum RunOrigin
+{
+    #[default]
+    New,
+    Loaded,
+}
+
 #[derive(Component)]
 struct Shape;
 
@@ -133,11 +206,6 @@ enum SimulateAction
     Quit,
 }
 
-#[derive(Resource)]
-struct HeightValues {
-    values: Vec<Vec<f32>>,
-}
-
 // This function creates a camera (can be used for main app and subapp)
 fn camera_setup(mut commands: Commands)
 {
@@ -179,6 +247,7 @@ fn camera_setup(mut commands: Commands)
 //  1. When a button is pressed
 //  2. When the mouse hovers over a button
 //  3. When the mouse is not hovering over a button
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
 fn main_button_system
 (
     mut interaction_query: Query<
@@ -194,9 +263,13 @@ fn main_button_system
 
     mut commands: Commands,
 
-    mut entity_query: Query<(Entity, &Transform), With<Handle<Mesh>>>,
+    mut entity_query: Query<(Entity, &Transform), With<Shape>>,
     mut next_state: ResMut<NextState<AppState>>,
     mut button_query: Query<(Entity, &Style)>,
+    mut open_browser: EventWriter<OpenFileBrowser>,
+    mut open_folder_picker: EventWriter<OpenFolderPicker>,
+    config: Res<WorldConfig>,
+    settings: Res<Settings>,
 )
 {
     for (interaction, menu_action, mut border_color) in &mut interaction_query
@@ -213,9 +286,9 @@ fn main_button_system
                     {
                         // If the start button was pressed, close the main menu and start the simulation.
 
-                        // Delete the icosahedron
+                        // Delete the icosahedron and the boundary lines on it
                         let (entity, _) = entity_query.single_mut();
-                        commands.entity(entity).despawn();
+                        commands.entity(entity).despawn_recursive();
 
                         // Delete all buttons and labels
                         for (entity, _) in &mut button_query
@@ -223,14 +296,14 @@ fn main_button_system
                             commands.entity(entity).despawn();
                         }
 
-                        // Remove the height values resource
-                        commands.remove_resource::<HeightValues>();
-
-                        // Create a new height values resource
-                        let h = HeightValues { values: Vec::<Vec<f32>>::new() };
+                        // Clear the columns, render_setup fills them with a new flat globe
+                        commands.insert_resource(Columns::default());
 
-                        // Insert the new height values resource
-                        commands.insert_resource(h);
+                        // Start the run from the seed in the settings file, or a new one if it has none, everything
+                        // random in the simulation comes from this config
+                        // The rest of the config is kept from the last run, which is remembered in the settings file
+                        commands.insert_resource(config.for_new_run(settings.seed));
+                        commands.insert_resource(RunOrigin::New);
 
                         // Switch app states to start the simulation
                         next_state.set(AppState::Simulate);
@@ -239,11 +312,15 @@ fn main_button_system
                     MenuAction::LoadFile =>
                     {
                         // If the load file button was pressed, allow the user to select a simulation file to start.
+                        // The file browser is opened and filled in by the systems in loading.rs.
+                        open_browser.send(OpenFileBrowser);
                     }
 
                     MenuAction::SelectFolder =>
                     {
                         // If the select folder button was pressed, allow the user to select a folder to save simulation files.
+                        // The folder picker is opened and handled by the systems in folder_picker.rs.
+                        open_folder_picker.send(OpenFolderPicker);
                     }
                     
                     MenuAction::Quit =>
@@ -269,6 +346,7 @@ fn main_button_system
     }
 }
 
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
 fn simulate_button_system
 (
     mut interaction_query: Query<
@@ -285,6 +363,9 @@ fn simulate_button_system
     mut entity_query: Query<(Entity, &Transform), With<Shape>>,
     mut next_state: ResMut<NextState<AppState>>,
     mut button_query: Query<(Entity, &Style)>,
+    mut clock: ResMut<SimulationClock>,
+    mut step_back: EventWriter<StepBack>,
+    mut save_request: EventWriter<SaveRequest>,
 )
 {
     for (interaction, simulate_action, mut border_color) in &mut interaction_query
@@ -300,25 +381,30 @@ fn simulate_button_system
                     SimulateAction::Pause =>
                     {
                         // If the pause button was pressed, stop the simulation and replace it with a play button.
+                        // Pressing it again resumes the simulation. update_clock_text swaps the glyph.
+                        clock.toggle();
                     }
 
                     SimulateAction::StepBack =>
                     {
                         // If the step back button was pressed, move the simulation back one state.
+                        step_back.send(StepBack);
                     }
 
                     SimulateAction::Save =>
                     {
                         // If the save button was pressed, save all the data of the simulation's current state.
+                        // The file is written in the background by start_save and finish_saves.
+                        save_request.send(SaveRequest);
                     }
                     
                     SimulateAction::Quit =>
                     {
                         // If the quit button was pressed, go back to the main menu
 
-                        // Delete the icosahedron
+                        // Delete the icosahedron and the boundary lines on it
                         let (entity, _) = entity_query.single_mut();
-                        commands.entity(entity).despawn();
+                        commands.entity(entity).despawn_recursive();
 
                         // Delete all buttons and labels
                         for (entity, _) in &mut button_query
@@ -326,14 +412,8 @@ fn simulate_button_system
                             commands.entity(entity).despawn();
                         }
 
-                        // Remove the height values resource
-                        commands.remove_resource::<HeightValues>();
-
-                        // Create a new height values resource
-                        let h = HeightValues { values: Vec::<Vec<f32>>::new() };
-
-                        // Insert the new height values resource
-                        commands.insert_resource(h);
+                        // Clear the columns, render_setup fills them with a new flat globe
+                        commands.insert_resource(Columns::default());
 
                         next_state.set(AppState::MainMenu);
                     }
@@ -769,6 +849,44 @@ fn simulate_gui
     (
         |parent|
         {
+            parent.spawn
+            (
+                (
+                    // Create the geologic time and speed readout to the left of the buttons
+                    TextBundle::from_section
+                    (
+                        // Filled in by update_clock_text
+                        "",
+
+                        // Set the style of the readout
+                        TextStyle
+                        {
+                            // Set the font of the text to default
+                            font: default(),
+
+                            // Set the font size of the text
+                            font_size: 20.0,
+
+                            // Set the color of the text
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                    )
+
+                    // Leave a gap between the readout and the pause button
+                    .with_style
+                    (
+                        Style
+                        {
+                            margin: UiRect::right(Val::Px(10.0)),
+
+                            ..default()
+                        }
+                    ),
+
+                    ClockText,
+                )
+            );
+
             parent.spawn
             (
                 (
@@ -814,24 +932,28 @@ fn simulate_gui
                 {
                     parent.spawn
                     (
-                        // Create the text within the button
-                        TextBundle::from_section
                         (
-                            // Set the text of the button
-                            "||",
-
-                            // Set the style of the text of the button
-                            TextStyle
-                            {
-                                // Set the font of the text to default
-                                font: default(),
-
-                                // Set the font size of the text
-                                font_size: 20.0,
-
-                                // Set the color of the text
-                                color: Color::rgb(0.9, 0.9, 0.9),
-                            },
+                            // Create the text within the button
+                            TextBundle::from_section
+                            (
+                                // Set the text of the button, swapped for the play glyph while paused
+                                PAUSE_GLYPH,
+
+                                // Set the style of the text of the button
+                                TextStyle
+                                {
+                                    // Set the font of the text to default
+                                    font: default(),
+
+                                    // Set the font size of the text
+                                    font_size: 20.0,
+
+                                    // Set the color of the text
+                                    color: Color::rgb(0.9, 0.9, 0.9),
+                                },
+                            ),
+
+                            PauseText,
                         )
                     );
                 }
@@ -1045,12 +1167,16 @@ fn simulate_gui
     );
 }
 
+#[allow(clippy::too_many_arguments)]
 fn render_setup(
 	mut commands: Commands,
 	mut materials: ResMut<Assets<StandardMaterial>>,
 	mut meshes: ResMut<Assets<Mesh>>,
     //mut images: ResMut<Assets<Image>>,
-    h: ResMut<HeightValues>,
+    mut columns: ResMut<Columns>,
+    grid: Res<Grid>,
+    config: Res<WorldConfig>,
+    display: Res<DisplayLayer>,
     current_state: ResMut<State<AppState>>,
 ) {
     //not certain what this is doing, this is probably where we want to start doing visuals
@@ -1058,26 +1184,29 @@ fn render_setup(
         ..default()
     });
 
-    //let mut heights = &h.values;
-    //let mut heights :std::vec::Vec<Vec<Vec<f32>>> = Vec::<Vec<Vec<f32>>>::new();
     //this is the call to create the mesh, and where we create what i think is basically a pointer to it
-    let globe_mesh_handle: Handle<Mesh> = meshes.add(create_globe_rect_mesh(100, 100, &mut h.into_inner().values));
-
-    let world_pos: [f32; 3];
+    //fills the columns with a flat globe of bare ocean crust at height 1 unless they are already there (loaded from a save)
+    if !columns.fits(&grid) {
+        *columns = Columns::new(grid.cell_count(), RockType::Basalt, &config);
+        columns.bedrock.fill(1.);
+    }
+    let mut globe_mesh = grid.mesh(&columns.heights());
+    globe_mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, layer_colors(display.0, &columns, &config));
+    let globe_mesh_handle: Handle<Mesh> = meshes.add(globe_mesh);
 
-    match current_state.get()
+    let world_pos: [f32; 3] = match current_state.get()
     {
         AppState::MainMenu =>
         {
-            world_pos = [3., -1., 0.];
+            [3., -1., 0.]
         }
         
         AppState::Simulate =>
         {
-            world_pos = [0., 0., 0.];
+            [0., 0., 0.]
         }
-    }
-    //loads mesh into scene
+    };
+    //loads mesh into scene, with the plate boundary lines hanging under it so they turn with the globe
 	commands.spawn((
         PbrBundle {
 		    mesh: globe_mesh_handle,
@@ -1086,20 +1215,28 @@ fn render_setup(
 		    ..Default::default()
         },
         Shape,
-	));
+	)).with_children(|globe| {
+        globe.spawn((
+            PbrBundle {
+                mesh: meshes.add(empty_overlay_mesh()),
+                material: materials.add(StandardMaterial { unlit: true, ..default() }),
+                ..default()
+            },
+            BoundaryOverlay,
+        ));
+    });
 }
 
-//lets you spin the mesh with X/Y/Z keys
+//lets you spin the mesh with X/Y/Z keys (or whatever they are bound to in the settings)
 fn input_handler(
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut mesh_query: Query<&Handle<Mesh>, With<Shape>>,
-    mut meshes: ResMut<Assets<Mesh>>,
+    settings: Res<Settings>,
     mut query: Query<&mut Transform, With<Shape>>,
-    h: ResMut<HeightValues>,
+    mut columns: ResMut<Columns>,
     time: Res<Time>,
 ) {
     
-    if keyboard_input.pressed(KeyCode::KeyX) {
+    if keyboard_input.pressed(settings.keys.rotate_x) {
         for mut transform in &mut query {
             transform.rotate_x(time.delta_seconds() / 1.2);
         }
@@ -1109,38 +1246,27 @@ fn input_handler(
     //        transform.rotate_y(time.delta_seconds() / 1.2);
     //    }
     //}
-    if keyboard_input.pressed(KeyCode::KeyZ) {
+    if keyboard_input.pressed(settings.keys.rotate_z) {
         for mut transform in &mut query {
             transform.rotate_z(time.delta_seconds() / 1.2);
         }
     }
 
-    let heights = &mut h.into_inner().values;
-    let mut changed: bool = false;
+    //only borrow the heights mutably when a key actually changes them, so refresh_globe_mesh doesn't rebuild every frame
+    //scaling both the bedrock and the sediment scales the height of the ground
     if keyboard_input.just_pressed(KeyCode::ArrowUp){
-        changed = true;
-        for i in 0..heights.len(){
-            for j in 0..heights[i].len(){
-                heights[i][j] = heights[i][j] * 1.1;
-		    }
+        let columns = &mut *columns;
+        for height in columns.bedrock.iter_mut().chain(columns.sediment.iter_mut()){
+            *height *= 1.1;
 	    }
 	}
 
     if keyboard_input.just_pressed(KeyCode::ArrowDown){
-        changed = true;
-        for i in 0..heights.len(){
-            for j in 0..heights[i].len(){
-			    heights[i][j] = heights[i][j] * 1.1;
-		    }
+        let columns = &mut *columns;
+        for height in columns.bedrock.iter_mut().chain(columns.sediment.iter_mut()){
+		    *height *= 1.1;
 	    }
 	}
-
-    if changed {
-        for mesh in &mut mesh_query{
-            let mesh_mut = meshes.get_mut(mesh);
-            mesh_mut.unwrap().insert_attribute(Mesh::ATTRIBUTE_POSITION, tris_from_rect_heights(heights));
-        }
-    }
 				//vs[i][0] = vs[i][0] * (1. + 0.25 * time.delta_seconds().cos());
 			
         
@@ -1148,23 +1274,35 @@ fn input_handler(
 
 }
 
+//pushes the current heights and layer colors into the globe mesh whenever they change (keyboard, plate motion, ...)
+fn refresh_globe_mesh(
+    mut mesh_query: Query<&Handle<Mesh>, With<Shape>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    columns: Res<Columns>,
+    grid: Res<Grid>,
+    config: Res<WorldConfig>,
+    display: Res<DisplayLayer>,
+) {
+    if !columns.fits(&grid) {
+        return;
+    }
+    for mesh in &mut mesh_query{
+        if let Some(mesh_mut) = meshes.get_mut(mesh) {
+            mesh_mut.insert_attribute(Mesh::ATTRIBUTE_POSITION, grid.positions(&columns.heights()));
+            mesh_mut.insert_attribute(Mesh::ATTRIBUTE_COLOR, layer_colors(display.0, &columns, &config));
+        }
+    }
+}
 
-fn create_globe_rect_mesh(h_verts: u32, v_verts: u32, heights: &mut Vec<Vec<f32>>) -> Mesh {
-    for _row_index in 0..v_verts{ //represents which row we are in
-		let mut row_vec = vec![];
-		for _col_index in 0..h_verts{		
-			row_vec.push(1.);
-            //println!("height at row {} and col {}", _row_index, _col_index);
-		}
-		heights.push(row_vec);
-	}
 
-    let verts = tris_from_rect_heights(heights);
+//heights are laid out like the lat/long Grid, one per vertex
+fn create_globe_rect_mesh(h_verts: u32, v_verts: u32, heights: &[f32]) -> Mesh {
+    let verts = tris_from_rect_heights(heights, v_verts as usize, h_verts as usize);
     let mut norms = Vec::new();
 
-    for i in 0..verts.len(){
-		norms.push(verts[i]);
-        //println!("vertex at {}: {}, {}, {}", i, verts[i][0], verts[i][1], verts[i][2]);
+    for vert in &verts{
+		norms.push(*vert);
+        //println!("vertex at: {}, {}, {}", vert[0], vert[1], vert[2]);
 	}
 
 
@@ -1217,35 +1355,39 @@ fn create_globe_rect_mesh(h_verts: u32, v_verts: u32, heights: &mut Vec<Vec<f32>
 }
 
 
-fn tris_from_rect_heights(heights: &mut Vec<Vec<f32>>) -> Vec<[f32; 3]>{
+fn tris_from_rect_heights(heights: &[f32], rows: usize, cols: usize) -> Vec<[f32; 3]>{
     let mut verts: Vec<[f32; 3]> = Vec::new();
 
-    verts.push([0., heights[0][0], 0.]); //index 0
+    verts.push([0., heights[0], 0.]); //index 0
      
-    for i in 1..(heights.len()-1){ //i is which row, y coord. indeces 1 to h_verts * (v_verts - 2)
-        let v_val: f32 = (i as f32)/(heights.len() as f32 - 1.);
+    for i in 1..(rows-1){ //i is which row, y coord. indeces 1 to h_verts * (v_verts - 2)
+        let v_val: f32 = (i as f32)/(rows as f32 - 1.);
         //println!("Adding row {}", i);
-		for j in 0..heights[i].len(){ //j is which column, x coord
-            let h_angle: f32 = 2. * std::f32::consts::PI * (j as f32)/(heights[i].len() as f32);
-			verts.push([heights[i][j] * h_angle.cos() * (0.25 - (v_val-0.5) * (v_val-0.5)).sqrt() * 2., heights[i][j] * (1. - 2. * v_val), heights[i][j] * h_angle.sin() * (0.25 - (v_val-0.5) * (v_val-0.5)).sqrt() * 2.]);
+		for j in 0..cols{ //j is which column, x coord
+            let h_angle: f32 = 2. * std::f32::consts::PI * (j as f32)/(cols as f32);
+            let height = heights[1 + (i-1)*cols + j];
+			verts.push([height * h_angle.cos() * (0.25 - (v_val-0.5) * (v_val-0.5)).sqrt() * 2., height * (1. - 2. * v_val), height * h_angle.sin() * (0.25 - (v_val-0.5) * (v_val-0.5)).sqrt() * 2.]);
             //println!("adding vert with coords ({}, {}, {})", verts[verts.len()-1][0], verts[verts.len()-1][1], verts[verts.len()-1][2]);
 		}
 	}
 
-    verts.push([0., -heights[heights.len()-1][0], 0.]); // index h_verts * (v_verts - 1) + 1
+    verts.push([0., -heights[heights.len()-1], 0.]); // index h_verts * (v_verts - 2) + 1
 
     //format of tris:
     //[row 0 vert 0, row1 vert 0, ... row1 vert h_verts-1, row2 vert 0 ... row v_verts-2 vert h_verts-1, row v_verts-1 vert 0]
 
-    return verts;
+    verts
 }
 
+// decides what goes into a snapshot and how one is applied, the files themselves are read and written by save_file.rs
+#[allow(dead_code)]
 struct SavePipeline;
 
 // Save Pipeline
 impl Pipeline for SavePipeline {
-    type Backend = DefaultDebugBackend;
-    type Format = DefaultDebugFormat;
+    //only here because Pipeline needs them, nothing goes through the backend
+    type Backend = DefaultBackend;
+    type Format = DefaultFormat;
 
     type Key<'a> = &'a str;
 
@@ -1257,7 +1399,12 @@ impl Pipeline for SavePipeline {
         builder
             //.deny::<Mesh2dHandle>()
             .deny::<Handle<ColorMaterial>>()
-            .extract_resource::<HeightValues>()
+            .extract_resource::<Columns>()
+            .extract_resource::<Plates>()
+            .extract_resource::<WorldConfig>()
+            .extract_resource::<SimulationClock>()
+            .extract_resource::<PlateEvents>()
+            .extract_entities_matching(|entity| entity.contains::<Hotspot>())
             .extract_rollbacks()
             .build()
     }
@@ -1265,505 +1412,54 @@ impl Pipeline for SavePipeline {
     fn apply(world: &mut World, snapshot: &Snapshot) -> Result<(), bevy_save::Error> {
         snapshot
             .applier(world)
+            .despawn::<With<Hotspot>>()
             .apply()
     }
 }
 
- //Erosion code
-
-    /*
-    //returns the height of the water, bed sediment, and bedrock. The suspended sediment is not included in this calculation because it is dispereced throughout the water
-    fn totalHeight (x : i32, y : i32) -> f32 {
-        return h[x][y].water + h[x][y].bed_sediment + h[x][y].bedrock;
-    }
-
-    //returns the total volume of water flowing from the tile
-    fn totalOutflowVolume (x: i32, y : i32) -> f32 {
-        return h[x][y].flow.n_volume + h[x][y].flow.e_volume + h[x][y].flow.s_volume + h[x][y].flow.w_volume;
-    }
-
-    //adds the amount of percipitation the tile experiences to the water level
-    fn precipitation(x : i32, y : i32, percipitation: f32) {
-        h[x][y].water += percipitation;
-    }
-
-    //evaluates the flow of water from the tile to its neighbors
-    fn waterFlow (x : i32, y : i32, n_height: f32, e_height: f32, s_height: f32, w_height: f32) {
-        let mut newFlow = h[x][y].flow.n_volume + h[x][y].flow.n_speed * h[x][y].totalHeight - n_height;
-        if (newFlow > 0) {
-            flow.n_volume = newFlow;
-        }
-        else {
-            flow.n_volume = 0;
-        }
-        newFlow = h[x][y].flow.e_volume + h[x][y].flow.e_speed * h[x][y].totalHeight - e_height;
-        if (newFlow > 0) {
-            flow.e_volume = newFlow;
-        }
-        else {
-            flow.e_volume = 0;
-        }
-        newFlow = h[x][y].flow.s_volume + h[x][y].flow.s_speed * h[x][y].totalHeight - s_height;
-        if (newFlow > 0) {
-            flow.s_volume = newFlow;
-        }
-        else {
-            flow.s_volume = 0;
-        }
-        newFlow = h[x][y].flow.w_volume + h[x][y].flow.w_speed * h[x][y].totalHeight - w_height;
-        if (newFlow > 0) {
-            flow.w_volume = newFlow;
-        }
-        else {
-            flow.w_volume = 0;
-        }
-    }
-
-    //calculates the scaler value for the flowScaling function
-    fn scaler (x : i32, y : i32) -> f32 {
-        let newScaler = h[x][y].water / (h[x][y].flow.n_volume + h[x][y].flow.e_volume + h[x][y].flow.s_volume + h[x][y].flow.w_volume);
-        if (newScaler > 1) {
-            h[x][y].scaler = 1; 
-        } else {
-            h[x][y].scaler = newScaler;
-        }
-    }
-
-    //makes sure that the amount of water flowing out of the tile is proportional to the amount of water flowing in
-    fn flowScaling (x : i32, y : i32) {
-        let scaler = h[x][y].scaler();
-        flow.n_volume = scaler * h[x][y].flow.n_volume;
-        flow.e_volume = scaler * h[x][y].flow.e_volume;
-        flow.s_volume = scaler * h[x][y].flow.s_volume;
-        flow.w_volume = scaler * h[x][y].flow.w_volume;
-    }
-
-    //changes the water level of the tile based on the flow of water to and from its neighbors
-    fn waterLevelUpdate (x : i32, y : i32, north: f32, east: f32, south: f32, west: f32) {
-        h[x][y].water = h[x][y].water + north + east + south + west - h[x][y].flow.n_volume - h[x][y].flow.e_volume - h[x][y].flow.s_volume - h[x][y].flow.w_volume;
-    }
-
-    //average speed of the water flowing in and out of the tile
-    fn waterSpeed (x : i32, y : i32, north: f32, east: f32, south: f32, west: f32) -> f32{
-        return (h[x][y].flowspeed = h[x][y].flowspeed + north + east + south + west - h[x][y].flow.n_speed - h[x][y].flow.e_speed - h[x][y].flow.s_speed - h[x][y].flow.w_speed) / 2;
-    }
-
-    //finds the slope of the tile based on the height of the tile and its neighbors
-    fn slope (x : i32, y : i32, north: f32, east: f32, south: f32, west: f32) -> f32 {
-        let slope = h[x][y].getTotalHeight - north;
-
-        if (slope < h[x][y].getTotalHeight - east) {
-            slope = h[x][y].getTotalHeight - east;
-        }
-
-        if (slope < h[x][y].getTotalHeight - south) {
-            slope = h[x][y].getTotalHeight - south;
-        }
-
-        if (slope < h[x][y].getTotalHeight - west) {
-            slope = h[x][y].getTotalHeight - west;
-        }
-        return slope;
-    }
-
-    //represents how much power the water has to erode sediment, higher values should result in more erosion and dissolution and less deposition
-    fn power (x : i32, y : i32, waterSpeed: f32, slope: f32) -> f32 {
-        return waterSpeed.sqrtf() * slope;
-    }
-
-    //the amount of sediment the water can carry
-    fn capacity (x : i32, y : i32, waterSpeed: f32, slope: f32) -> f32{
-        return 1 * waterSpeed.sqrtf() * slope;
-    }
-
-    //deposit suspended sediment into the bed sediment
-    fn depositSediment (x : i32, y : i32, pScaler: f32) {
-        let transferredSediment = (h[x][y].suspended_sediment - h[x][y].capacity) * (1 / h[x][y].power.sqrtf() * pScaler);
-        h[x][y].bed_sediment += transferredSediment;
-        h[x][y].suspended_sediment -= transferredSediment;
-    }
-
-    //erode bed sediment into the suspended sediment
-    fn disolveSediment (x : i32, y : i32, pScaler: f32){
-        let transferredSediment = (h[x][y].capacity - h[x][y].suspended_sediment) * (h[x][y].power.sqrtf() * pScaler);
-        h[x][y].suspended_sediment += transferredSediment;
-        h[x][y].bed_sediment -= transferredSediment;
-    }
-
-    //erode bedrock into the suspended sediment
-    fn erodeBedrock (x : i32, y : i32, target: f32) {
-        let transferredSediment = (h[x][y].capacity - h[x][y].suspended_sediment) * (h[x][y].power.sqrtf() * pScaler);
-        h[x][y].suspended_sediment += transferredSediment;
-        h[x][y].bedrock -= transferredSediment;
-    }
-
-    //handles the erosion, deposition, and dissolution of sediment
-    fn erosion (x : i32, y : i32, target: f32) {
-        //deposition
-        if (h[x][y].suspended_sediment > h[x][y].capacity * target) {
-            depositSediment(1);
-        }
-        //erosion (if there insufficient bed sediment)
-        else if (h[x][y].bed_sediment < capacity * .05;) {
-            erodeSediment(1);
-        }
-        //dissolution
-        else {
-            disolveSediment(1);
-        }
-    }
-
-    //TODO: implement this function
-    fn sedimentTransfer(x : i32, y : i32, north: tile, east: tile, south: tile, west: tile) {
-        /*
-        waterLevelUpdate = h[x][y].waterLevelUpdate();
-        totalFlowVolume = h[x][y].totalFlowVolume();
-        water = h[x][y].water;
-        suspended_sediment = h[x][y].suspended_sediment;
-        h[x][y].suspended_sediment = suspended_sediment + north.waterLevelUpdate / north.water * north.flow.s_volume / north.getTotalFlowVolume * north.suspended_sediment -
-        waterLevelUpdate / water * h[x][y].flow.n_volume / totalFlowVolume * suspended_sediment +
-        east.waterLevelUpdate / east.water * east.flow.w_volume / east.getTotalFlowVolume * east.suspended_sediment -
-        waterLevelUpdate / water * h[x][y].flow.e_volume / totalFlowVolume * suspended_sediment +
-        south.waterLevelUpdate / south.water * south.flow.n_volume / south.getTotalFlowVolume * south.suspended_sediment -
-        waterLevelUpdate / water * h[x][y].flow.s_volume / totalFlowVolume * suspended_sediment +
-        west.waterLevelUpdate / west.water * west.flow.e_volume / west.getTotalFlowVolume * west.suspended_sediment -
-        waterLevelUpdate / water * h[x][y].flow.w_volume / totalFlowVolume * suspended_sediment;
-        */
-    }
-
-    //finds the tile directly north of the given tile coordinates, if the tile is at the north pole, it will return the tile opposite the pole.
-    fn getNorth (x : i32, y : i32, worldHeight : i32, worldWidth : i32) -> tile {
-        if (y == worldHeight - 1) {
-            return h[x+(worldWidth/2)%worldWidth][y];
-        }
-        else {
-            return h[x][y + 1];
-        }
-    }
-
-    fn getEast (x : i32, y : i32, worldWidth : i32) -> tile {
-        return h[(x + 1)%worldWidth][y];
-    }
+#[cfg(test)]
+mod tests
+{
+    use super::*;
 
-    fn getSouth (x : i32, y : i32, worldHeight : i32, worldWidth : i32) -> tile {
-        if (y == 0) {
-            return h[x+(worldWidth/2)%worldWidth][y];
-        }
-        else {
-            return h[x][y - 1];
+    // runs a new world from the config for some ticks the way the app does, with nothing drawn
+    fn run(config: &WorldConfig, ticks: usize) -> Columns
+    {
+        let mut world = World::new();
+        world.insert_resource(config.clone());
+        world.init_resource::<Grid>();
+        world.init_resource::<Columns>();
+        world.init_resource::<Plates>();
+        world.init_resource::<ErosionState>();
+        world.init_resource::<PlateBoundaries>();
+        world.init_resource::<PlateEvents>();
+        world.init_resource::<Flexure>();
+        world.init_resource::<SimulationClock>();
+        world.init_resource::<SimulationHistory>();
+        world.init_resource::<Settings>();
+
+        let mut setup = Schedule::default();
+        setup.add_systems((grid_setup, erosion_setup, flexure_setup, clock_setup, plates_setup, terrain_setup, isostasy_setup, plate_events_setup, hotspots_setup, history_setup, classify_boundaries).chain());
+        setup.run(&mut world);
+
+        let mut tick = Schedule::default();
+        tick.add_systems(simulation_tick());
+        for _ in 0..ticks
+        {
+            tick.run(&mut world);
         }
+        world.remove_resource::<Columns>().unwrap()
     }
 
-    fn getWest (x : i32, y : i32, worldWidth : i32) -> tile {
-        return h[(x - 1)%worldWidth][y];
-    }
+    #[test]
+    fn same_config_same_run()
+    {
+        let config = WorldConfig { seed: 1234, rows: 40, cols: 40, ..default() };
+        let first = run(&config, 40);
+        assert!(first.reflect_partial_eq(&run(&config, 40)) == Some(true));
 
-    //preforms all the functions needed to update the tile for one time step
-    fn step(x : i32, y : i32, north: tile, east: tile, south: tile, west: tile) {
-        h[x][y].precipitation();
-        h[x][y].waterFlow(north.height, east.height, south.height, west.height);
-        h[x][y].scaler();
-        h[x][y].flowScaling();
-        h[x][y].waterLevelUpdate(north.flow.s_volume, east.flow.w_volume, south.flow.n_volume, west.flow.e_volume);
-        waterSpeed = h[x][y].waterSpeed(north.flow.s_speed, east.flow.w_speed, south.flow.n_speed, west.flow.e_speed);
-        slope = h[x][y].slope(north.height, east.height, south.height, west.height);
-        h[x][y].power(slope, waterSpeed);
-        h[x][y].capacity(slope, waterSpeed);
-        h[x][y].erosion(.05);
+        // another seed makes another world, so the columns really are compared
+        let reseeded = config.for_new_run(Some(1235));
+        assert!(first.reflect_partial_eq(&run(&reseeded, 40)) == Some(false));
     }
-    */
-
-
-
-//#[rustfmt::skip]
-//fn create_globe_ico_mesh(subdivisions: u32, heights: &mut Vec<Vec<Vec<f32>>>,) -> Mesh {
-//    let res = 2_i32.pow(subdivisions); //short for resolution, this is just an important value used many places
-//    //vertex positions vector calculation:
-//    let mut i = 0;
-//    while i<4{
-//        let mut j = 0;
-//        let mut row_vec = vec![];
-//        while j<5{
-//			let mut v = 0;
-//            let mut col_vec = vec![];
-//            while v<(res+1)*(res+2)/2 {
-//                col_vec.push(1.);
-//                v+=1;
-//            }
-//            row_vec.push(col_vec);
-//			j+=1;
-//		}
-//        heights.push(row_vec);
-//        i +=1;
-//    }
-//
-//    //println!("Heights: {:?}", heights);
-//    
-//    
-//
-//
-//
-//    let verts = tris_from_ico_heights(heights);
-//    
-//    let mut norms = Vec::new();//create vector to hold calculated normals
-//
-//    i = 0;
-//    while i < verts.len(){//TODO: neither of these approaches will work once verts have variable heights
-//        //if you want to use the other type of normal comment out all code relevant to the current type
-//        //unsmoothed normals
-//        
-//        let x = (verts[i][0] + verts[i+1][0] + verts[i+2][0])/3.;
-//        let y = (verts[i][1] + verts[i+1][1] + verts[i+2][1])/3.;
-//        let z = (verts[i][2] + verts[i+1][2] + verts[i+2][2])/3.;
-//        let d1 = (x*x+y*y+z*z).sqrt();
-//        norms.push([x/d1, y/d1, z/d1]);
-//        norms.push([x/d1, y/d1, z/d1]);
-//        norms.push([x/d1, y/d1, z/d1]);
-//        i = i+3;
-//        
-//        //smoothed normals
-//        //norms.push([verts[i][0]/d, verts[i][1]/d, verts[i][2]/d]);
-//        //i=i+1;
-//    }
-//
-//    //put verts into mesh
-//    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD)
-//    .with_inserted_attribute(
-//        Mesh::ATTRIBUTE_POSITION,
-//        // Each array is an [x, y, z] coordinate in local space.
-//        // Meshes always rotate around their local [0, 0, 0] when a rotation is applied to their Transform.
-//        // By centering our mesh around the origin, rotating the mesh preserves its center of mass.
-//        verts.clone(),
-//    )
-//
-//    //put normals into mesh
-//    .with_inserted_attribute(
-//        Mesh::ATTRIBUTE_NORMAL,
-//        norms,
-//    )
-//    
-//    //tell mesh which verts are connected
-//    .with_inserted_indices(Indices::U32((0u32..(verts.len() as u32)).collect::<Vec<_>>()))
-//}
-//
-//fn tris_from_ico_heights(heights: &mut Vec<Vec<Vec<f32>>>) -> Vec<[f32; 3]>{
-//
-//    //useful numbers: p represents phi, d is the magnitude of vert on a major triangle 
-//    let p: f32 = (1.0 + (5.0_f32).sqrt()) / 2.0;
-//    let d: f32 = (1.0 + p *p).sqrt();
-//    let res: i32 = (((1 + 8*heights[0][0].len()) as f64).sqrt() as i32 - 3)/2;
-//    //created to use later
-//    let main_verts = vec![
-//        [0., d, 0.],
-//
-//        [0., 0.85065, -1.7013],
-//        [p, 0.85065, -(d*d - p * p - 0.85065 * 0.85065).sqrt()],
-//		[1., 0.85065, (d*d - 1. - 0.85065*0.85065).sqrt()],
-//		[-1., 0.85065, (d*d - 1. - 0.85065*0.85065).sqrt()],
-//		[-p, 0.85065, -(d*d - p*p - 0.85065*0.85065).sqrt()],
-//
-//        [-1., -0.85065, -(d*d - 1. - 0.85065*0.85065).sqrt()],
-//        [1., -0.85065, -(d*d - 1. - 0.85065*0.85065).sqrt()],
-//        [p, -0.85065, (d*d - p*p - 0.85065*0.85065).sqrt()],
-//        [0., -0.85065, 1.7013],
-//        [-p, -0.85065, (d*d - p*p - 0.85065*0.85065).sqrt()],
-//
-//        [0., -d, 0.]
-//    ];
-//
-//    let mut verts = Vec::new();
-//    
-//    let mut f = 0; //which face
-//    while f<5 { //iterates over the 5 major triangles which share the topmost vert
-//        let v0 = main_verts[0];//c vert
-//        let v1 = main_verts[f+1];//b vert
-//        let v2 = main_verts[((f+1)%5)+1];//a vert
-//
-//        let mut col_index = 0;
-//        while col_index < res{
-//            let mut row_index = 0;
-//            while row_index <= col_index*2{
-//                
-//                let mut iter = 0;
-//                let mut vert0 = [0., 0., 0.];//c vert
-//                let mut vert1 = [0., 0., 0.];//a vert
-//                let mut vert2 = [0., 0., 0.];//b vert
-//
-//                if row_index%2==0{
-//                    while iter<3{
-//                        vert0[iter] = ((res-col_index) as f32 * v0[iter] + (col_index - row_index/2) as f32 * v1[iter] + (row_index/2) as f32 * v2[iter])/res as f32;
-//                        vert1[iter] = ((res-col_index - 1) as f32 * v0[iter] + (col_index + 1 - row_index/2) as f32 * v1[iter] + (row_index/2) as f32 * v2[iter])/res as f32;
-//                        vert2[iter] = ((res-col_index - 1) as f32 * v0[iter] + (col_index - row_index/2) as f32 * v1[iter] + (row_index/2 + 1) as f32 * v2[iter])/res as f32;
-//
-//                        iter = iter+1;
-//                    }
-//                } else {
-//                    while iter<3{
-//                        vert0[iter] = ((res-col_index - 1) as f32 * v0[iter] + (col_index - (row_index-1)/2) as f32 * v1[iter] + ((row_index-1)/2 + 1) as f32 * v2[iter])/res as f32;
-//                        vert1[iter] = ((res-col_index) as f32 * v0[iter] + (col_index - (row_index+1)/2) as f32 * v1[iter] + ((row_index+1)/2) as f32 * v2[iter])/res as f32;
-//                        vert2[iter] = ((res-col_index) as f32 * v0[iter] + (col_index - (row_index-1)/2) as f32 * v1[iter] + ((row_index-1)/2) as f32 * v2[iter])/res as f32;
-//                        
-//                        iter = iter+1;
-//                    }
-//                }
-//
-//                let len0 = (vert0[0]*vert0[0] + vert0[1]*vert0[1] + vert0[2]*vert0[2]).sqrt();
-//                let len1 = (vert1[0]*vert1[0] + vert1[1]*vert1[1] + vert1[2]*vert1[2]).sqrt();
-//                let len2 = (vert2[0]*vert2[0] + vert2[1]*vert2[1] + vert2[2]*vert2[2]).sqrt();
-//                
-//                //order is weird, but important
-//                //values correspond to:
-//                //right vert
-//                //vertical vert
-//                //left vert
-//                verts.push([vert1[0] * get_height(&heights, f, 1+row_index/2,   col_index-(row_index+1)/2)/len1,   vert1[1]*get_height(&heights, f, 1+row_index/2,   col_index-(row_index+1)/2)/len1,   vert1[2]*get_height(&heights, f, 1+row_index/2,   col_index-(row_index+1)/2)/len1]);
-//                verts.push([vert0[0] * get_height(&heights, f, (row_index+1)/2, col_index-row_index/2)/len0,       vert0[1]*get_height(&heights, f, (row_index+1)/2, col_index-row_index/2)/len0,       vert0[2]*get_height(&heights, f, (row_index+1)/2, col_index-row_index/2)/len0]);
-//                verts.push([vert2[0] * get_height(&heights, f, row_index/2,     col_index+1-(row_index+1)/2)/len2, vert2[1]*get_height(&heights, f, row_index/2,     col_index+1-(row_index+1)/2)/len2, vert2[2]*get_height(&heights, f, row_index/2,     col_index+1-(row_index+1)/2)/len2]);
-//
-//
-//                row_index = row_index+1;
-//            }
-//            col_index=col_index+1;
-//            
-//        }
-//
-//
-//        f = f+1;
-//    }
-//
-//    let vert_order = [//which vertices to use for each major triangle not on the top or bottom
-//        [6,1,7],
-//        [7,1,2],
-//        [7,2,8],
-//		[8,2,3],
-//		[8,3,9],
-//		[9,3,4],
-//		[9,4,10],
-//		[10,4,5],
-//		[10,5,6],
-//		[6,5,1]
-//    ];
-//    f = 0;
-//
-//    while f< 10{ //iterate through the 10 major triangles that do not have the topmost or bottommost vert
-//        let v0 = main_verts[vert_order[f][0]];//f%2==0: b vert, f%2==1: c vert
-//        let v1 = main_verts[vert_order[f][1]];//f%2==0: c vert, f%2==1: b vert
-//        let v2 = main_verts[vert_order[f][2]];//a vert
-//
-//        let mut col_index = 0;
-//        while col_index < res{
-//            let mut row_index = 0;
-//            while row_index <= 2*col_index{
-//                
-//                let mut iter = 0;
-//                let mut vert0 = [0., 0., 0.];
-//                let mut vert1 = [0., 0., 0.];
-//                let mut vert2 = [0., 0., 0.];
-//
-//                if row_index%2==0{
-//                    while iter<3{
-//                        vert0[iter] = ((res-col_index) as f32 * v0[iter] + (col_index - row_index/2) as f32 * v1[iter] + (row_index/2) as f32 * v2[iter])/res as f32;
-//                        vert1[iter] = ((res-col_index - 1) as f32 * v0[iter] + (col_index + 1 - row_index/2) as f32 * v1[iter] + (row_index/2) as f32 * v2[iter])/res as f32;
-//                        vert2[iter] = ((res-col_index - 1) as f32 * v0[iter] + (col_index - row_index/2) as f32 * v1[iter] + (row_index/2 + 1) as f32 * v2[iter])/res as f32;
-//
-//                        iter = iter+1;
-//                    }
-//
-//                    
-//                } else {
-//                    while iter<3{
-//                        vert0[iter] = ((res-col_index - 1) as f32 * v0[iter] + (col_index - (row_index-1)/2) as f32 * v1[iter] + ((row_index-1)/2 + 1) as f32 * v2[iter])/res as f32;
-//                        vert1[iter] = ((res-col_index) as f32 * v0[iter] + (col_index - (row_index+1)/2) as f32 * v1[iter] + ((row_index+1)/2) as f32 * v2[iter])/res as f32;
-//                        vert2[iter] = ((res-col_index) as f32 * v0[iter] + (col_index - (row_index-1)/2) as f32 * v1[iter] + ((row_index-1)/2) as f32 * v2[iter])/res as f32;
-//                        
-//                        iter = iter+1;
-//                    }
-//                }
-//
-//                let len0 = (vert0[0]*vert0[0] + vert0[1]*vert0[1] + vert0[2]*vert0[2]).sqrt();
-//                let len1 = (vert1[0]*vert1[0] + vert1[1]*vert1[1] + vert1[2]*vert1[2]).sqrt();
-//                let len2 = (vert2[0]*vert2[0] + vert2[1]*vert2[1] + vert2[2]*vert2[2]).sqrt();
-//                
-//                
-//                //i am... surprised that this just works for both orientations of mega-tris
-//                //but not complaining
-//                //left corner
-//                //vertical corner
-//                //right corner
-//                verts.push([vert0[0] * get_height(&heights, f+5, row_index/2,     col_index+1-(row_index+1)/2)/len0, vert0[1]*get_height(&heights, f+5, row_index/2,     col_index+1-(row_index+1)/2)/len0, vert0[2]*get_height(&heights, f+5, row_index/2,     col_index+1-(row_index+1)/2)/len0]);
-//                verts.push([vert1[0] * get_height(&heights, f+5, (row_index+1)/2, col_index-row_index/2)/len1, vert1[1]*get_height(&heights, f+5, (row_index+1)/2, col_index-row_index/2)/len1, vert1[2]*get_height(&heights, f+5, (row_index+1)/2, col_index-row_index/2)/len1]);
-//                verts.push([vert2[0] * get_height(&heights, f+5, 1+row_index/2,   col_index-(row_index+1)/2)/len2, vert2[1]*get_height(&heights, f+5, 1+row_index/2,   col_index-(row_index+1)/2)/len2, vert2[2]*get_height(&heights, f+5, 1+row_index/2,   col_index-(row_index+1)/2)/len2]);
-//                
-//
-//                row_index = row_index+1;
-//            }
-//            col_index=col_index+1;
-//            
-//        }
-//
-//
-//        f = f+1;
-//    }
-//    
-//    f = 0;
-//    while f<5 {//iterate over 5 major tris that share the bottommost vert
-//        let v0 = main_verts[11];//c vert
-//        let v1 = main_verts[10-f];//a vert
-//        let v2 = main_verts[10-((f+1)%5)];//b vert
-//
-//        let mut col_index = 0;
-//        while col_index < res{
-//            let mut row_index = 0;
-//            while row_index <= 2*col_index{
-//                
-//                let mut iter = 0;
-//                let mut vert0 = [0., 0., 0.];
-//                let mut vert1 = [0., 0., 0.];
-//                let mut vert2 = [0., 0., 0.];
-//
-//                if row_index%2==0{
-//                    while iter<3{
-//                        vert0[iter] = ((res-col_index) as f32 * v0[iter] + (col_index - row_index/2) as f32 * v1[iter] + (row_index/2) as f32 * v2[iter])/res as f32;
-//                        vert1[iter] = ((res-col_index - 1) as f32 * v0[iter] + (col_index + 1 - row_index/2) as f32 * v1[iter] + (row_index/2) as f32 * v2[iter])/res as f32;
-//                        vert2[iter] = ((res-col_index - 1) as f32 * v0[iter] + (col_index - row_index/2) as f32 * v1[iter] + (row_index/2 + 1) as f32 * v2[iter])/res as f32;
-//
-//                        iter = iter+1;
-//                    }
-//
-//                    
-//                } else {
-//                    while iter<3{
-//                        vert0[iter] = ((res-col_index - 1) as f32 * v0[iter] + (col_index - (row_index-1)/2) as f32 * v1[iter] + ((row_index-1)/2 + 1) as f32 * v2[iter])/res as f32;
-//                        vert1[iter] = ((res-col_index) as f32 * v0[iter] + (col_index - (row_index+1)/2) as f32 * v1[iter] + ((row_index+1)/2) as f32 * v2[iter])/res as f32;
-//                        vert2[iter] = ((res-col_index) as f32 * v0[iter] + (col_index - (row_index-1)/2) as f32 * v1[iter] + ((row_index-1)/2) as f32 * v2[iter])/res as f32;
-//                        
-//                        iter = iter+1;
-//                    }
-//                }
-//
-//                let len0 = (vert0[0]*vert0[0] + vert0[1]*vert0[1] + vert0[2]*vert0[2]).sqrt();
-//                let len1 = (vert1[0]*vert1[0] + vert1[1]*vert1[1] + vert1[2]*vert1[2]).sqrt();
-//                let len2 = (vert2[0]*vert2[0] + vert2[1]*vert2[1] + vert2[2]*vert2[2]).sqrt();
-//                
-//                //order is weird, but important
-//                verts.push([vert1[0] * get_height(&heights, f+15, row_index/2,     col_index+1-(row_index+1)/2)/len1,   vert1[1]*get_height(&heights, f+15, row_index/2,     col_index+1-(row_index+1)/2)/len1, vert1[2]*get_height(&heights, f+15, row_index/2,     col_index+1-(row_index+1)/2)/len1]);
-//                verts.push([vert0[0] * get_height(&heights, f+15, (row_index+1)/2, col_index-row_index/2)/len0,         vert0[1]*get_height(&heights, f+15, (row_index+1)/2, col_index-row_index/2)/len0, vert0[2]*get_height(&heights, f+15, (row_index+1)/2, col_index-row_index/2)/len0]);
-//                verts.push([vert2[0] * get_height(&heights, f+15, 1+row_index/2,   col_index-(row_index+1)/2)/len2,     vert2[1]*get_height(&heights, f+15, 1+row_index/2,   col_index-(row_index+1)/2)/len2, vert2[2]*get_height(&heights, f+15, 1+row_index/2,   col_index-(row_index+1)/2)/len2]);
-//
-//
-//                row_index = row_index+1;
-//            }
-//            col_index=col_index+1;
-//            
-//        }
-//
-//
-//        f = f+1;
-//    }
-//    return verts;
-//}
-//
-//fn get_height(heights: &Vec<Vec<Vec<f32>>>, major_tri: usize, a: i32, b: i32) -> f32{
-//    //magic formula !!! who knows why it works but it does!!! i could probably re-derive it but please dont make me
-//    return heights[(major_tri/5) as usize][(major_tri%5) as usize][(a+(b+a)*(b+a+1)/2) as usize];
-//}
\ No newline at end of file
+}
@@ -0,0 +1,286 @@
+// Subduction and mountain building
+//
+// Where two plates close in on each other the denser crust dives under the lighter one. Oceanic crust gets denser as
+// it ages, so it sinks under continents and under younger ocean floor alike: a trench opens on the sinking side and
+// a line of volcanoes rises on the other, some way back from the boundary (the Andes, or island arcs where both sides
+// are oceanic). Continental crust is too light to sink, so when two continents meet the crust crumples and thickens
+// and the thicker crust floats higher (the Himalaya).
+//
+// Everything happens faster the faster the plates converge. Trenches deepen faster the bigger the density contrast
+// pulling the slab down, and growth slows as trenches and mountains approach their limits.
+
+use bevy::prelude::*;
+
+use crate::{
+    boundaries::{BoundaryKind, PlateBoundaries},
+    columns::{Columns, RockType},
+    config::WorldConfig,
+    grid::Grid,
+};
+
+// Settings for subduction and collisions, part of WorldConfig. Densities are in g/cm³, heights are relative to a
+// globe radius of 1 like everywhere else
+#[derive(Reflect, Clone, Debug)]
+pub struct OrogenySettings {
+    pub enabled: bool,
+
+    pub granite_density: f32,
+    pub mantle_density: f32,
+
+    // density of new oceanic crust, and how much denser it gets every million years as it cools
+    pub basalt_density: f32,
+    pub basalt_density_per_myr: f32,
+
+    // height lost by the sinking side, gained by the arc, and crust thickness gained in a collision, for every globe
+    // radius the plates converge
+    pub trench_rate: f32,
+    pub arc_rate: f32,
+    pub collision_rate: f32,
+
+    // how far back from the boundary the volcanic arc reaches, in radians
+    pub arc_distance: f32,
+
+    // deepest a trench gets below new ocean floor, and highest mountains get above sea level
+    pub max_trench_depth: f32,
+    pub max_elevation: f32,
+}
+
+impl Default for OrogenySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            granite_density: 2.7,
+            mantle_density: 3.3,
+            basalt_density: 2.9,
+            basalt_density_per_myr: 0.0005,
+            trench_rate: 2.0,
+            arc_rate: 0.05,
+            collision_rate: 0.3,
+            arc_distance: 0.08,
+            max_trench_depth: 0.04,
+            max_elevation: 0.06,
+        }
+    }
+}
+
+impl OrogenySettings {
+    pub fn density(&self, rock_type: RockType, age: f32) -> f32 {
+        match rock_type {
+            RockType::Basalt => self.basalt_density + self.basalt_density_per_myr * age,
+            RockType::Granite => self.granite_density,
+        }
+    }
+
+    // how hard a slab sinking under crust of another density pulls, from nothing when it is no denser than that crust
+    // to full strength when it is as dense as the mantle
+    pub fn slab_pull(&self, density: f32, other_density: f32) -> f32 {
+        ((density - other_density) / (self.mantle_density - other_density)).clamp(0., 1.)
+    }
+}
+
+// what happens to a cell's crust at a convergent boundary
+enum Role {
+    Sinking,
+    Overriding,
+    Colliding,
+}
+
+// one tick of subduction and collision along the convergent boundaries found by classify_boundaries
+pub fn orogeny(
+    config: Res<WorldConfig>,
+    grid: Res<Grid>,
+    boundaries: Res<PlateBoundaries>,
+    mut columns: ResMut<Columns>,
+) {
+    let settings = &config.orogeny;
+    if !settings.enabled || !columns.fits(&grid) {
+        return;
+    }
+
+    //changes are collected first so each boundary cell sees the crust as it was at the start of the tick
+    //cells near several boundary cells take the strongest push rather than adding them up, so the result doesn't
+    //depend on how many cells the boundary is split into
+    let cell_count = grid.cell_count();
+    let mut sinking = vec![0f32; cell_count];
+    let mut arc = vec![0f32; cell_count];
+    let mut thickening = vec![0f32; cell_count];
+
+    for cell in boundaries.cells(BoundaryKind::Convergent) {
+        //the neighbor across the fastest converging edge decides the cell's role
+        let Some(edge) = boundaries
+            .at(cell)
+            .iter()
+            .filter(|edge| edge.kind == BoundaryKind::Convergent)
+            .max_by(|a, b| a.convergence.total_cmp(&b.convergence))
+        else {
+            continue;
+        };
+        let neighbor = edge.neighbor as usize;
+        let closed = edge.convergence * config.myr_per_tick;
+
+        let density = settings.density(columns.rock_type[cell], columns.crust_age[cell]);
+        let other_density = settings.density(columns.rock_type[neighbor], columns.crust_age[neighbor]);
+        let role = if columns.rock_type[cell] == RockType::Granite && columns.rock_type[neighbor] == RockType::Granite {
+            Role::Colliding
+        } else if density > other_density || (density == other_density && cell > neighbor) {
+            Role::Sinking
+        } else {
+            Role::Overriding
+        };
+
+        match role {
+            Role::Sinking => {
+                let pull = settings.slab_pull(density, other_density);
+                sinking[cell] = sinking[cell].max(settings.trench_rate * closed * pull);
+            }
+            Role::Overriding => {
+                //the arc is highest over the boundary and fades out towards arc_distance
+                let dir = grid.direction(cell);
+                let plate = columns.plate_id[cell];
+                for target in cells_within(&grid, &columns, cell, plate, settings.arc_distance) {
+                    let falloff = 1. - dir.angle_between(grid.direction(target)) / settings.arc_distance;
+                    arc[target] = arc[target].max(settings.arc_rate * closed * falloff);
+                }
+            }
+            Role::Colliding => {
+                thickening[cell] = thickening[cell].max(settings.collision_rate * closed);
+            }
+        }
+    }
+
+    for cell in 0..cell_count {
+        let height = columns.height(cell);
+
+        if sinking[cell] > 0. {
+            let depth = config.oceanic_crust_height - height;
+            columns.bedrock[cell] -= sinking[cell] * (1. - depth / settings.max_trench_depth).clamp(0., 1.);
+        }
+
+        let headroom = (1. - (height - config.erosion.sea_level) / settings.max_elevation).clamp(0., 1.);
+
+        //arc volcanoes pile new rock on top of the crust
+        if arc[cell] > 0. {
+            let added = arc[cell] * headroom;
+            columns.bedrock[cell] += added;
+            columns.crust_thickness[cell] += added;
+        }
+
+        //thickened crust floats higher by the part of the extra thickness the mantle it displaces doesn't make up for
+        if thickening[cell] > 0. {
+            let added = thickening[cell] * headroom;
+            let density = settings.density(columns.rock_type[cell], columns.crust_age[cell]);
+            columns.crust_thickness[cell] += added;
+            columns.bedrock[cell] += added * (1. - density / settings.mantle_density);
+        }
+    }
+}
+
+// cells of a plate within an angle of a cell, found by walking out from it across the plate
+fn cells_within(grid: &Grid, columns: &Columns, cell: usize, plate: u32, angle: f32) -> Vec<usize> {
+    let dir = grid.direction(cell);
+    let mut found = vec![cell];
+    let mut next = 0;
+    while next < found.len() {
+        let current = found[next];
+        next += 1;
+        for &neighbor in grid.neighbors(current) {
+            let neighbor = neighbor as usize;
+            if columns.plate_id[neighbor] == plate
+                && !found.contains(&neighbor)
+                && dir.angle_between(grid.direction(neighbor)) <= angle
+            {
+                found.push(neighbor);
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::{
+        boundaries::classify_boundaries,
+        grid::GridKind,
+        plates::{advance_plates, Plate, Plates},
+    };
+
+    // ocean floor 50 Myr old east of the meridian x = 0 turning west into a still continent at +z
+    fn ocean_meets_continent() -> World {
+        let config = WorldConfig { grid: GridKind::CubeSphere, cube_face_cells: 16, ..default() };
+        let grid = Grid::from_config(&config);
+        let mut columns = Columns::new(grid.cell_count(), RockType::Basalt, &config);
+        for cell in 0..grid.cell_count() {
+            if grid.direction(cell).x > 0. {
+                columns.crust_age[cell] = 50.;
+            } else {
+                columns.new_crust(cell, RockType::Granite, &config);
+                columns.plate_id[cell] = 1;
+            }
+        }
+        let plates = vec![
+            Plate { id: 0, pole: Vec3::Y, angular_velocity: -0.01, ..default() },
+            Plate { id: 1, pole: Vec3::Y, angular_velocity: 0., ..default() },
+        ];
+
+        let mut world = World::new();
+        world.insert_resource(config);
+        world.insert_resource(grid);
+        world.insert_resource(columns);
+        world.insert_resource(Plates { plates });
+        world.init_resource::<PlateBoundaries>();
+        world.run_system_once(classify_boundaries);
+        world
+    }
+
+    #[test]
+    fn ocean_sinks_under_continent_and_raises_an_arc() {
+        let mut world = ocean_meets_continent();
+        let before = world.resource::<Columns>().clone();
+        world.run_system_once(orogeny);
+        let (grid, columns, boundaries) =
+            (world.resource::<Grid>(), world.resource::<Columns>(), world.resource::<PlateBoundaries>());
+
+        let front: Vec<usize> =
+            boundaries.cells(BoundaryKind::Convergent).filter(|&cell| grid.direction(cell).z > 0.5).collect();
+        for plate in [0, 1] {
+            let on_front = front.iter().any(|&cell| columns.plate_id[cell] == plate);
+            assert!(on_front, "plate {} is not on the boundary", plate);
+        }
+        for &cell in &front {
+            if columns.plate_id[cell] == 0 {
+                assert!(columns.bedrock[cell] < before.bedrock[cell], "no trench at ocean cell {}", cell);
+            } else {
+                assert!(columns.bedrock[cell] > before.bedrock[cell], "no arc at continent cell {}", cell);
+                assert!(columns.crust_thickness[cell] > before.crust_thickness[cell]);
+            }
+        }
+
+        //away from the boundary nothing happens
+        for cell in (0..grid.cell_count()).filter(|&cell| grid.direction(cell).x.abs() > 0.3) {
+            assert_eq!(columns.bedrock[cell], before.bedrock[cell], "cell {} is far from the boundary", cell);
+        }
+    }
+
+    #[test]
+    fn ocean_is_consumed_under_continent() {
+        let mut world = ocean_meets_continent();
+        let before = world.resource::<Columns>().clone();
+        for _ in 0..20 {
+            world.run_system_once(advance_plates);
+            world.run_system_once(classify_boundaries);
+            world.run_system_once(orogeny);
+        }
+        let (grid, columns) = (world.resource::<Grid>(), world.resource::<Columns>());
+
+        //the continent keeps every cell it had, and the old ocean floor that ran into it is gone
+        for cell in (0..grid.cell_count()).filter(|&cell| before.plate_id[cell] == 1) {
+            assert_eq!((columns.plate_id[cell], columns.rock_type[cell]), (1, RockType::Granite), "cell {}", cell);
+        }
+        let old_ocean = |columns: &Columns| columns.crust_age.iter().filter(|&&age| age >= 50.).count();
+        let (left, had) = (old_ocean(columns), old_ocean(&before));
+        assert!(left < had, "{} of {} old ocean cells left", left, had);
+    }
+}
@@ -0,0 +1,572 @@
+// Tectonic plate model
+//
+// Every cell of the globe's grid belongs to exactly one plate. Plates rotate rigidly about their own
+// pole, and each fixed tick the crust (its column below the water) is carried along to the cells it now sits over.
+
+use std::collections::BinaryHeap;
+
+use bevy::prelude::*;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    columns::{Columns, RockType},
+    config::{WorldConfig, PLATE_STREAM},
+    grid::Grid,
+};
+
+#[derive(Reflect, Clone, Debug, Default)]
+pub struct Plate {
+    // index of the plate inside Plates, also the plate id stored in the Columns of each of its cells
+    pub id: u32,
+
+    // unit vector the plate rotates about
+    pub pole: Vec3,
+
+    // radians per million years, positive is counter clockwise looking down on the pole
+    pub angular_velocity: f32,
+
+    // rotation built up since the crust was last moved to new cells
+    pub pending_angle: f32,
+
+    // effective elastic thickness of the plate's lithosphere in km, how stiffly it bends under loads
+    pub elastic_thickness: f32,
+}
+
+impl Plate {
+    // velocity of the plate's crust at a point on the globe, in globe radii per million years
+    pub fn velocity_at(&self, dir: Vec3) -> Vec3 {
+        self.pole.cross(dir) * self.angular_velocity
+    }
+
+    // the pole scaled by the angular velocity, rotations about different poles can be added up this way
+    pub fn rotation(&self) -> Vec3 {
+        self.pole * self.angular_velocity
+    }
+
+    pub fn set_rotation(&mut self, rotation: Vec3) {
+        if let Some(pole) = rotation.try_normalize() {
+            self.pole = pole;
+            self.angular_velocity = rotation.length();
+        } else {
+            self.angular_velocity = 0.;
+        }
+    }
+}
+
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource)]
+pub struct Plates {
+    pub plates: Vec<Plate>,
+}
+
+impl Plates {
+    // moves some cells of a plate onto a new plate that starts out moving the same way, and returns the new plate's id
+    pub fn split_off(&mut self, columns: &mut Columns, plate: u32, cells: &[usize]) -> u32 {
+        let id = self.plates.len() as u32;
+        let mut new_plate = self.plates[plate as usize].clone();
+        new_plate.id = id;
+        new_plate.pending_angle = 0.;
+        self.plates.push(new_plate);
+
+        for &cell in cells {
+            columns.plate_id[cell] = id;
+        }
+        id
+    }
+}
+
+// Settings for the procedural plate layout made when a run starts, part of WorldConfig
+#[derive(Reflect, Clone, Debug)]
+pub struct PlateGenSettings {
+    // number of plates scattered over the globe
+    pub plate_count: usize,
+
+    // 0 gives plates of roughly equal size, values near 1 give a few huge plates and many small ones
+    pub size_variance: f32,
+
+    // fraction of the globe's cells that start as continental crust
+    pub continental_fraction: f32,
+}
+
+// defaults to a dozen or so plates of mixed size with about 30% continents, similar to present day Earth
+// something like 30 plates, 0.1 variance and 0.7 continental gives a much stranger world
+impl Default for PlateGenSettings {
+    fn default() -> Self {
+        Self {
+            plate_count: 12,
+            size_variance: 0.6,
+            continental_fraction: 0.3,
+        }
+    }
+}
+
+// entry in the flood fill queue, ordered so the BinaryHeap pops the smallest weighted distance first
+struct GrowthFront {
+    cost: f32,
+    plate: u32,
+    cell: usize,
+}
+
+impl PartialEq for GrowthFront {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost.total_cmp(&other.cost).is_eq()
+    }
+}
+
+impl Eq for GrowthFront {}
+
+impl PartialOrd for GrowthFront {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for GrowthFront {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+// picks a point uniformly distributed over the unit sphere
+pub fn random_unit_vector(rng: &mut impl Rng) -> Vec3 {
+    let y: f32 = rng.gen_range(-1.0..1.0);
+    let angle: f32 = rng.gen_range(0.0..(2. * std::f32::consts::PI));
+    let ring_radius = (1. - y * y).sqrt();
+    Vec3::new(angle.cos() * ring_radius, y, angle.sin() * ring_radius)
+}
+
+// splits the cells of a grid into plates, and covers each plate with new oceanic or continental crust
+// seeds are scattered on the sphere and grown outwards by flood fill, each plate's distances are divided by a
+// random weight so the result is a weighted Voronoi diagram whose plates are always connected
+pub fn generate_plates(config: &WorldConfig, grid: &Grid) -> (Vec<Plate>, Columns) {
+    let settings = &config.plates;
+    let mut rng = config.rng(PLATE_STREAM);
+    let plate_count = settings.plate_count.max(1);
+
+    let mut centers = Vec::with_capacity(plate_count);
+    let mut weights = Vec::with_capacity(plate_count);
+    let mut plates = Vec::with_capacity(plate_count);
+    for k in 0..plate_count {
+        centers.push(random_unit_vector(&mut rng));
+        weights.push(1. + settings.size_variance * rng.gen_range(-0.9..0.9));
+        plates.push(Plate {
+            id: k as u32,
+            pole: random_unit_vector(&mut rng),
+            angular_velocity: rng.gen_range(-0.03..0.03),
+            pending_angle: 0.,
+            elastic_thickness: 0.,
+        });
+    }
+
+    let cell_count = grid.cell_count();
+    let mut plate_ids = vec![u32::MAX; cell_count];
+    let mut front = BinaryHeap::new();
+    for (k, center) in centers.iter().enumerate() {
+        front.push(GrowthFront { cost: 0., plate: k as u32, cell: grid.cell_from_direction(*center) });
+    }
+
+    while let Some(GrowthFront { plate, cell, .. }) = front.pop() {
+        if plate_ids[cell] != u32::MAX {
+            continue;
+        }
+        plate_ids[cell] = plate;
+
+        for &neighbor in grid.neighbors(cell) {
+            let neighbor = neighbor as usize;
+            if plate_ids[neighbor] == u32::MAX {
+                let cost = grid.direction(neighbor).angle_between(centers[plate as usize]) / weights[plate as usize];
+                front.push(GrowthFront { cost, plate, cell: neighbor });
+            }
+        }
+    }
+
+    //turn randomly chosen plates into continents until enough of the globe's surface is covered
+    //sizes go by area rather than cell count, cells are not the same size on every grid
+    let mut plate_areas = vec![0f32; plate_count];
+    for (cell, &id) in plate_ids.iter().enumerate() {
+        plate_areas[id as usize] += grid.cell_area(cell);
+    }
+    let mut order: Vec<usize> = (0..plate_count).collect();
+    order.shuffle(&mut rng);
+    let target = settings.continental_fraction.clamp(0., 1.) * plate_areas.iter().sum::<f32>();
+    let mut continental = vec![false; plate_count];
+    let mut covered = 0.;
+    for k in order {
+        if covered >= target {
+            break;
+        }
+        continental[k] = true;
+        covered += plate_areas[k];
+    }
+    for (plate, &continental) in plates.iter_mut().zip(&continental) {
+        plate.elastic_thickness = config.flexure.elastic_thickness(continental);
+    }
+
+    let mut columns = Columns::new(cell_count, RockType::Basalt, config);
+    for (cell, &id) in plate_ids.iter().enumerate() {
+        columns.plate_id[cell] = id;
+        if continental[id as usize] {
+            columns.new_crust(cell, RockType::Granite, config);
+        }
+    }
+
+    (plates, columns)
+}
+
+// builds a fresh set of plates over the globe made by render_setup, covered in new crust at its base elevation
+pub fn plates_setup(
+    config: Res<WorldConfig>,
+    grid: Res<Grid>,
+    mut columns: ResMut<Columns>,
+    mut plates: ResMut<Plates>,
+) {
+    let (new_plates, new_columns) = generate_plates(&config, &grid);
+
+    plates.plates = new_plates;
+    *columns = new_columns;
+}
+
+// share of a cell crust has to cover to take it
+const MIN_COVER: f32 = 0.5;
+
+// Crust from one plate landing on a cell, summed over the cells it came from
+// everything is weighted by the area of the source cell that lands here, so dividing by `area` gives averages and
+// the sums themselves are volumes that can be kept track of
+#[derive(Clone, Copy, Default)]
+struct Landing {
+    plate: u32,
+    area: f32,
+    thickness: f32,
+    base: f32,
+    sediment: f32,
+    age: f32,
+    granite: f32,
+}
+
+impl Landing {
+    fn add(&mut self, columns: &Columns, source: usize, area: f32) {
+        let thickness = columns.crust_thickness[source];
+        self.area += area;
+        self.thickness += thickness * area;
+        self.base += (columns.bedrock[source] - thickness) * area;
+        self.sediment += columns.sediment[source] * area;
+        self.age += columns.crust_age[source] * area;
+        if columns.rock_type[source] == RockType::Granite {
+            self.granite += area;
+        }
+    }
+
+    fn rock_type(&self) -> RockType {
+        if self.granite * 2. >= self.area {
+            RockType::Granite
+        } else {
+            RockType::Basalt
+        }
+    }
+
+    fn age(&self) -> f32 {
+        self.age / self.area
+    }
+}
+
+// decides which of two pieces of crust stays on top when both land on the same cell
+// the lighter crust floats over the denser one, so continents always stay up, and between crust of the same density
+// the older, stiffer crust wins
+fn overrides(config: &WorldConfig, a: &Landing, b: &Landing) -> bool {
+    let a_density = config.orogeny.density(a.rock_type(), a.age());
+    let b_density = config.orogeny.density(b.rock_type(), b.age());
+    match a_density.total_cmp(&b_density) {
+        std::cmp::Ordering::Less => true,
+        std::cmp::Ordering::Greater => false,
+        std::cmp::Ordering::Equal => a.age() > b.age(),
+    }
+}
+
+// ages the crust and moves it across the sphere, run on the fixed timestep
+// the sediment lying on the crust travels with it, water stays where it is
+//
+// every cell of a moving plate is turned about the plate's Euler pole and shared out between the cells around where
+// it lands, by how close it lands to them. Where crust from several plates lands on a cell the lightest stays on top,
+// the crust under it is subducted, or stacked onto it when both are continental, and the sediment on it is scraped
+// off onto the crust that stays. Cells nobody lands on get new ocean floor. Averaging the shares would make a
+// plate's crust a little thicker or thinner than before, depending on how the cells happen to line up, so each
+// plate's crust and sediment is scaled back to the volume that actually landed
+pub fn advance_plates(
+    config: Res<WorldConfig>,
+    grid: Res<Grid>,
+    mut columns: ResMut<Columns>,
+    mut plates: ResMut<Plates>,
+) {
+    if !columns.fits(&grid) {
+        return;
+    }
+    let cell_count = grid.cell_count();
+
+    for age in &mut columns.crust_age {
+        *age += config.myr_per_tick;
+    }
+
+    //crust only changes cells once a plate has rotated at least one cell's worth, otherwise it would be smeared out a
+    //little more every tick
+    let cell_angle = grid.cell_angle();
+    let mut rotations = Vec::with_capacity(plates.plates.len());
+    let mut any_moved = false;
+    for plate in &mut plates.plates {
+        plate.pending_angle += plate.angular_velocity * config.myr_per_tick;
+        if plate.pending_angle.abs() >= cell_angle {
+            rotations.push(Some(Quat::from_axis_angle(plate.pole, plate.pending_angle)));
+            plate.pending_angle = 0.;
+            any_moved = true;
+        } else {
+            rotations.push(None);
+        }
+    }
+    if !any_moved {
+        return;
+    }
+
+    let old = columns.clone();
+    let mut landings: Vec<Vec<Landing>> = vec![Vec::new(); cell_count];
+    for source in 0..cell_count {
+        let plate = old.plate_id[source];
+        let area = grid.cell_area(source);
+        let mut land = |cell: usize, share: f32| {
+            let landing = match landings[cell].iter_mut().position(|landing| landing.plate == plate) {
+                Some(index) => &mut landings[cell][index],
+                None => {
+                    landings[cell].push(Landing { plate, ..default() });
+                    landings[cell].last_mut().unwrap()
+                }
+            };
+            landing.add(&old, source, area * share);
+        };
+
+        let Some(rotation) = rotations.get(plate as usize).copied().flatten() else {
+            land(source, 1.);
+            continue;
+        };
+
+        //shared between the nearest cell and its neighbors, each by how much closer it is than the farthest neighbor
+        let dir = rotation * grid.direction(source);
+        let nearest = grid.cell_from_direction(dir);
+        let nearest_dir = grid.direction(nearest);
+        let neighbors = grid.neighbors(nearest);
+        let reach = neighbors
+            .iter()
+            .map(|&neighbor| nearest_dir.angle_between(grid.direction(neighbor as usize)))
+            .fold(0., f32::max);
+        let closeness = |cell: usize| (reach - dir.angle_between(grid.direction(cell))).max(0.);
+        let total = closeness(nearest) + neighbors.iter().map(|&neighbor| closeness(neighbor as usize)).sum::<f32>();
+        if total <= 0. {
+            land(nearest, 1.);
+            continue;
+        }
+        land(nearest, closeness(nearest) / total);
+        for &neighbor in neighbors {
+            let share = closeness(neighbor as usize) / total;
+            if share > 0. {
+                land(neighbor as usize, share);
+            }
+        }
+    }
+
+    //crust and sediment volume each plate brought along, less what was subducted or stacked onto another plate, and
+    //the volume the averages put on the cells the plate kept
+    let plate_count = plates.plates.len();
+    let mut landed = vec![(0f32, 0f32); plate_count];
+    let mut placed = vec![(0f32, 0f32); plate_count];
+    let mut stacked = vec![0f32; cell_count];
+    let mut scraped = vec![0f32; cell_count];
+    let mut taken = vec![false; cell_count];
+    for (cell, cell_landings) in landings.iter().enumerate() {
+        let area = grid.cell_area(cell);
+        let covers = |landing: &Landing| landing.area >= MIN_COVER * area;
+
+        //a thin sliver of crust along a plate's edge doesn't take a cell, its crust and sediment go back to the rest
+        //of its plate
+        for landing in cell_landings.iter().filter(|landing| !covers(landing)) {
+            if let Some(landed) = landed.get_mut(landing.plate as usize) {
+                landed.0 += landing.thickness;
+                landed.1 += landing.sediment;
+            }
+        }
+        let top = cell_landings.iter().filter(|landing| covers(landing)).copied().reduce(|top, landing| {
+            if overrides(&config, &landing, &top) {
+                landing
+            } else {
+                top
+            }
+        });
+        let Some(top) = top else {
+            //plates pulled apart here, fill the gap with new ocean floor belonging to the plate that left
+            columns.new_crust(cell, RockType::Basalt, &config);
+            continue;
+        };
+
+        for landing in cell_landings.iter().filter(|landing| landing.plate != top.plate && covers(landing)) {
+            if landing.rock_type() == RockType::Granite {
+                stacked[cell] += landing.thickness / area;
+            }
+            scraped[cell] += landing.sediment / area;
+        }
+
+        let thickness = top.thickness / top.area;
+        let sediment = top.sediment / top.area;
+        columns.crust_thickness[cell] = thickness;
+        columns.bedrock[cell] = top.base / top.area + thickness;
+        columns.sediment[cell] = sediment;
+        columns.crust_age[cell] = top.age();
+        columns.rock_type[cell] = top.rock_type();
+        columns.plate_id[cell] = top.plate;
+        taken[cell] = true;
+        if let (Some(landed), Some(placed)) = (landed.get_mut(top.plate as usize), placed.get_mut(top.plate as usize)) {
+            landed.0 += top.thickness;
+            landed.1 += top.sediment;
+            placed.0 += thickness * area;
+            placed.1 += sediment * area;
+        }
+    }
+
+    let scale = |landed: f32, placed: f32| if placed > 0. { landed / placed } else { 1. };
+    let buoyancy = 1. - config.orogeny.granite_density / config.orogeny.mantle_density;
+    for cell in (0..cell_count).filter(|&cell| taken[cell]) {
+        let plate = columns.plate_id[cell] as usize;
+        let (thickness_scale, sediment_scale) = match (landed.get(plate), placed.get(plate)) {
+            (Some(landed), Some(placed)) => (scale(landed.0, placed.0), scale(landed.1, placed.1)),
+            _ => (1., 1.),
+        };
+
+        //stacked continental crust goes mostly into the root, the way a collision thickens crust
+        let thickness = columns.crust_thickness[cell];
+        let base = columns.bedrock[cell] - thickness;
+        let scaled = thickness * thickness_scale;
+        columns.crust_thickness[cell] = scaled + stacked[cell];
+        columns.bedrock[cell] = base + scaled + stacked[cell] * buoyancy;
+        columns.sediment[cell] = columns.sediment[cell] * sediment_scale + scraped[cell];
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::grid::GridKind;
+
+    // a plate turning by one and a half cells in the next tick
+    fn turning_plate(id: u32, pole: Vec3, grid: &Grid) -> Plate {
+        Plate { id, pole: pole.normalize(), angular_velocity: 1.5 * grid.cell_angle(), ..default() }
+    }
+
+    // the columns after one tick of advance_plates
+    fn advance(config: &WorldConfig, columns: Columns, plates: Vec<Plate>) -> Columns {
+        let mut world = World::new();
+        world.insert_resource(config.clone());
+        world.insert_resource(Grid::from_config(config));
+        world.insert_resource(columns);
+        world.insert_resource(Plates { plates });
+        world.run_system_once(advance_plates);
+        world.remove_resource::<Columns>().unwrap()
+    }
+
+    // crust and sediment volume of a plate, leaving out the new ocean floor of cells nobody landed on
+    fn plate_volume(grid: &Grid, columns: &Columns, plate: u32, min_age: f32) -> (f64, f64) {
+        (0..grid.cell_count())
+            .filter(|&cell| columns.plate_id[cell] == plate && columns.crust_age[cell] >= min_age)
+            .map(|cell| {
+                let area = grid.cell_area(cell) as f64;
+                (columns.crust_thickness[cell] as f64 * area, columns.sediment[cell] as f64 * area)
+            })
+            .fold((0., 0.), |(crust, sediment), (a, b)| (crust + a, sediment + b))
+    }
+
+    #[test]
+    fn rotation_keeps_plate_volume() {
+        for kind in [GridKind::LatLong, GridKind::Icosahedral, GridKind::CubeSphere] {
+            let config =
+                WorldConfig { grid: kind, rows: 60, cols: 60, ico_subdivisions: 4, cube_face_cells: 16, ..default() };
+            let grid = Grid::from_config(&config);
+
+            //one plate over the whole globe, so nothing overlaps, carrying crust of every thickness
+            let mut rng = ChaCha8Rng::seed_from_u64(2);
+            let mut columns = Columns::new(grid.cell_count(), RockType::Basalt, &config);
+            for cell in 0..grid.cell_count() {
+                if rng.gen_bool(0.3) {
+                    columns.new_crust(cell, RockType::Granite, &config);
+                }
+                columns.crust_thickness[cell] *= rng.gen_range(0.5..1.5);
+                columns.sediment[cell] = rng.gen_range(0.0..0.01);
+                columns.crust_age[cell] = 50.;
+            }
+            let before = plate_volume(&grid, &columns, 0, 0.);
+
+            let plates = vec![turning_plate(0, Vec3::new(0.3, 0.8, 0.5), &grid)];
+            let moved = advance(&config, columns.clone(), plates);
+            let after = plate_volume(&grid, &moved, 0, 50.);
+            let kept = |before: f64, after: f64| ((after - before) / before).abs() < 1e-4;
+            assert!(kept(before.0, after.0), "{:?} crust: {} became {}", kind, before.0, after.0);
+            assert!(kept(before.1, after.1), "{:?} sediment: {} became {}", kind, before.1, after.1);
+            assert!(moved.crust_thickness != columns.crust_thickness, "{:?}: the plate did not move", kind);
+        }
+    }
+
+    fn landing(rock_type: RockType, age: f32) -> Landing {
+        let granite = if rock_type == RockType::Granite { 1. } else { 0. };
+        Landing { area: 1., age, granite, ..default() }
+    }
+
+    #[test]
+    fn lighter_then_older_crust_overrides() {
+        let config = WorldConfig::default();
+        let overrides = |a: Landing, b: Landing| overrides(&config, &a, &b);
+
+        //continents float over ocean floor of any age
+        assert!(overrides(landing(RockType::Granite, 0.), landing(RockType::Basalt, 0.)));
+        assert!(!overrides(landing(RockType::Basalt, 0.), landing(RockType::Granite, 200.)));
+
+        //ocean floor gets denser as it ages, so the younger floor stays up
+        assert!(overrides(landing(RockType::Basalt, 10.), landing(RockType::Basalt, 80.)));
+        assert!(!overrides(landing(RockType::Basalt, 80.), landing(RockType::Basalt, 10.)));
+
+        //continents are all as dense, the older one wins
+        assert!(overrides(landing(RockType::Granite, 80.), landing(RockType::Granite, 10.)));
+        assert!(!overrides(landing(RockType::Granite, 10.), landing(RockType::Granite, 80.)));
+    }
+
+    #[test]
+    fn collisions_keep_the_overriding_plate() {
+        let config = WorldConfig { grid: GridKind::Icosahedral, ico_subdivisions: 4, ..default() };
+        let grid = Grid::from_config(&config);
+
+        //plate 0 is the x > 0 half of the globe and turns about y, running into plate 1 around -z
+        let cases = [
+            (RockType::Granite, 50., RockType::Basalt, 50., true),
+            (RockType::Basalt, 50., RockType::Granite, 50., false),
+            (RockType::Basalt, 10., RockType::Basalt, 80., true),
+            (RockType::Basalt, 80., RockType::Basalt, 10., false),
+            (RockType::Granite, 80., RockType::Granite, 10., true),
+            (RockType::Granite, 10., RockType::Granite, 80., false),
+        ];
+        for (moving_rock, moving_age, still_rock, still_age, moving_wins) in cases {
+            let mut columns = Columns::new(grid.cell_count(), RockType::Basalt, &config);
+            for cell in 0..grid.cell_count() {
+                let moving = grid.direction(cell).x > 0.;
+                let (rock_type, age) = if moving { (moving_rock, moving_age) } else { (still_rock, still_age) };
+                columns.new_crust(cell, rock_type, &config);
+                columns.crust_age[cell] = age;
+                columns.plate_id[cell] = if moving { 0 } else { 1 };
+            }
+
+            let still = Plate { id: 1, pole: Vec3::Y, ..default() };
+            let moved = advance(&config, columns.clone(), vec![turning_plate(0, Vec3::Y, &grid), still]);
+            let taken = (0..grid.cell_count())
+                .filter(|&cell| columns.plate_id[cell] == 1 && moved.plate_id[cell] == 0)
+                .count();
+            let case = (moving_rock, moving_age, still_rock, still_age);
+            assert_eq!(taken > 0, moving_wins, "{:?}: the moving plate took {} cells", case, taken);
+        }
+    }
+}
@@ -0,0 +1,1101 @@
+// Save files
+//
+// A save file starts with a small uncompressed header (magic, format version, grid type and resolution) followed by
+// a zlib compressed body. The per-cell layers in the body are split into byte planes before compression, the high
+// bytes of neighbouring heights are nearly always equal so they squeeze down to almost nothing.
+//
+// Every format version keeps its own reader. A reader for an old version fills in whatever that version did not
+// store, so the state structs can change without breaking old saves: bump FORMAT_VERSION, write the new layout in
+// write_body, and add a reader for it to read_body next to the old ones. Only version 1 exists so far.
+//
+// Saves written before this format existed are bevy_save JSON snapshots of the lat/long heights, the plates, the plate
+// map, the world config and the clock. They count as version 0, read_legacy takes what they hold out of the JSON and
+// builds the rest the way a new run would.
+
+use std::{
+    io::{Read, Write},
+    path::Path,
+};
+
+use bevy::prelude::*;
+use bevy_save::prelude::*;
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::{
+    clock::SimulationClock,
+    columns::{Columns, RockType},
+    config::WorldConfig,
+    erosion::ErosionSettings,
+    flexure::FlexureSettings,
+    forces::PlateForceSettings,
+    grid::{grid_cell_count, GridKind},
+    hotspots::{Hotspot, HotspotSettings},
+    isostasy::{IsostasyModel, IsostasySettings},
+    lifecycle::{LifecycleSettings, PlateEvent, PlateEventKind, PlateEvents},
+    orogeny::OrogenySettings,
+    plates::{Plate, PlateGenSettings, Plates},
+    spreading::SpreadingSettings,
+    terrain::TerrainSettings,
+    thermal::{TalusSettings, ThermalSettings},
+    SavePipeline,
+};
+
+pub const SAVE_EXTENSION: &str = ".tsim";
+
+// saves made by older versions of the app
+const LEGACY_EXTENSION: &str = ".json";
+
+const MAGIC: [u8; 4] = *b"TECT";
+pub const FORMAT_VERSION: u16 = 1;
+
+fn grid_to_byte(grid: GridKind) -> u8 {
+    match grid {
+        GridKind::LatLong => 0,
+        GridKind::Icosahedral => 1,
+        GridKind::CubeSphere => 2,
+    }
+}
+
+fn grid_from_byte(byte: u8) -> Result<GridKind, String> {
+    match byte {
+        0 => Ok(GridKind::LatLong),
+        1 => Ok(GridKind::Icosahedral),
+        2 => Ok(GridKind::CubeSphere),
+        _ => Err(format!("unknown grid type {}", byte)),
+    }
+}
+
+fn rock_to_byte(rock: RockType) -> u8 {
+    match rock {
+        RockType::Basalt => 0,
+        RockType::Granite => 1,
+    }
+}
+
+fn rock_from_byte(byte: u8) -> Result<RockType, String> {
+    match byte {
+        0 => Ok(RockType::Basalt),
+        1 => Ok(RockType::Granite),
+        _ => Err(format!("unknown rock type {}", byte)),
+    }
+}
+
+fn isostasy_model_to_byte(model: IsostasyModel) -> u8 {
+    match model {
+        IsostasyModel::Airy => 0,
+        IsostasyModel::Pratt => 1,
+    }
+}
+
+fn isostasy_model_from_byte(byte: u8) -> Result<IsostasyModel, String> {
+    match byte {
+        0 => Ok(IsostasyModel::Airy),
+        1 => Ok(IsostasyModel::Pratt),
+        _ => Err(format!("unknown isostasy model {}", byte)),
+    }
+}
+
+// stored in place of the other plate of an event that has none
+const NO_PLATE: u32 = u32::MAX;
+
+fn event_kind_to_byte(kind: PlateEventKind) -> u8 {
+    match kind {
+        PlateEventKind::Split => 0,
+        PlateEventKind::Merge => 1,
+        PlateEventKind::Destroy => 2,
+    }
+}
+
+fn event_kind_from_byte(byte: u8) -> Result<PlateEventKind, String> {
+    match byte {
+        0 => Ok(PlateEventKind::Split),
+        1 => Ok(PlateEventKind::Merge),
+        2 => Ok(PlateEventKind::Destroy),
+        _ => Err(format!("unknown plate event {}", byte)),
+    }
+}
+
+pub struct SaveHeader {
+    pub version: u16,
+    pub grid: GridKind,
+
+    // see WorldConfig::grid_resolution
+    pub resolution: (u32, u32),
+}
+
+// Everything a save holds, in the shape the current version of the app uses
+pub struct SaveData {
+    pub config: WorldConfig,
+    pub clock: SimulationClock,
+    pub plates: Plates,
+    pub columns: Columns,
+    pub events: PlateEvents,
+    pub hotspots: Vec<Hotspot>,
+}
+
+// finds a resource of type T in a snapshot
+fn snapshot_resource<T: FromReflect + TypePath>(snapshot: &Snapshot) -> Result<T, String> {
+    snapshot
+        .resources
+        .iter()
+        .find(|resource| resource.represents::<T>())
+        .and_then(|resource| T::from_reflect(resource.as_ref()))
+        .ok_or_else(|| format!("the save has no {}", T::short_type_path()))
+}
+
+impl SaveData {
+    // pulls the saved resources out of a snapshot taken with SavePipeline
+    pub fn from_snapshot(snapshot: &Snapshot) -> Result<Self, String> {
+        Ok(Self {
+            config: snapshot_resource(snapshot)?,
+            clock: snapshot_resource(snapshot)?,
+            plates: snapshot_resource(snapshot)?,
+            columns: snapshot_resource(snapshot)?,
+            events: snapshot_resource(snapshot)?,
+            hotspots: snapshot
+                .entities
+                .iter()
+                .flat_map(|entity| &entity.components)
+                .filter(|component| component.represents::<Hotspot>())
+                .filter_map(|component| Hotspot::from_reflect(component.as_ref()))
+                .collect(),
+        })
+    }
+
+    // builds the snapshot SavePipeline::apply restores the simulation from
+    pub fn into_snapshot(self, registry: AppTypeRegistry) -> Snapshot {
+        let mut world = World::new();
+        world.insert_resource(registry);
+        world.init_resource::<RollbackRegistry>();
+        world.insert_resource(self.config);
+        world.insert_resource(self.clock);
+        world.insert_resource(self.plates);
+        world.insert_resource(self.columns);
+        world.insert_resource(self.events);
+        world.spawn_batch(self.hotspots);
+        SavePipeline::capture(Snapshot::builder(&world))
+    }
+
+    pub fn header(&self) -> SaveHeader {
+        SaveHeader {
+            version: FORMAT_VERSION,
+            grid: self.config.grid,
+            resolution: self.config.grid_resolution(),
+        }
+    }
+}
+
+// whether the file browser should list a file
+pub fn is_save_file(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    name.ends_with(SAVE_EXTENSION) || name.ends_with(LEGACY_EXTENSION)
+}
+
+// little endian writers for the body
+trait WriteBytes {
+    fn put_u8(&mut self, value: u8);
+    fn put_u16(&mut self, value: u16);
+    fn put_u32(&mut self, value: u32);
+    fn put_u64(&mut self, value: u64);
+    fn put_f32(&mut self, value: f32);
+    fn put_f64(&mut self, value: f64);
+}
+
+impl WriteBytes for Vec<u8> {
+    fn put_u8(&mut self, value: u8) {
+        self.push(value);
+    }
+    fn put_u16(&mut self, value: u16) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+    fn put_u32(&mut self, value: u32) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+    fn put_u64(&mut self, value: u64) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+    fn put_f32(&mut self, value: f32) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+    fn put_f64(&mut self, value: f64) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+// writes the lowest byte of every value, then the next byte of every value, and so on
+fn put_planes(out: &mut Vec<u8>, values: &[u32]) {
+    for byte in 0..4 {
+        out.extend(values.iter().map(|value| value.to_le_bytes()[byte]));
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < count {
+            return Err("the save file is cut short".to_string());
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+    fn f32(&mut self) -> Result<f32, String> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+    fn f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+
+    // the other half of put_planes
+    fn planes(&mut self, count: usize) -> Result<Vec<u32>, String> {
+        let planes = self.take(count.checked_mul(4).ok_or("the save file is cut short")?)?;
+        Ok((0..count)
+            .map(|index| {
+                u32::from_le_bytes([
+                    planes[index],
+                    planes[count + index],
+                    planes[2 * count + index],
+                    planes[3 * count + index],
+                ])
+            })
+            .collect())
+    }
+}
+
+fn write_header(out: &mut Vec<u8>, header: &SaveHeader) {
+    out.extend_from_slice(&MAGIC);
+    out.put_u16(header.version);
+    out.put_u8(grid_to_byte(header.grid));
+    out.put_u32(header.resolution.0);
+    out.put_u32(header.resolution.1);
+}
+
+fn read_header(reader: &mut Reader) -> Result<SaveHeader, String> {
+    if reader.array::<4>()? != MAGIC {
+        return Err("not a save file".to_string());
+    }
+    Ok(SaveHeader {
+        version: reader.u16()?,
+        grid: grid_from_byte(reader.u8()?)?,
+        resolution: (reader.u32()?, reader.u32()?),
+    })
+}
+
+// the body in the current format version
+fn write_body(out: &mut Vec<u8>, data: &SaveData) {
+    let config = &data.config;
+    out.put_u64(config.seed);
+    out.put_u8(grid_to_byte(config.grid));
+    out.put_u32(config.rows);
+    out.put_u32(config.cols);
+    out.put_u32(config.ico_subdivisions);
+    out.put_u32(config.cube_face_cells);
+    out.put_f64(config.timestep);
+    out.put_f32(config.myr_per_tick);
+    out.put_f32(config.oceanic_crust_height);
+    out.put_f32(config.continental_crust_height);
+    out.put_f32(config.oceanic_crust_thickness);
+    out.put_f32(config.continental_crust_thickness);
+    out.put_u32(config.plates.plate_count as u32);
+    out.put_f32(config.plates.size_variance);
+    out.put_f32(config.plates.continental_fraction);
+    out.put_u32(config.terrain.octaves as u32);
+    out.put_f64(config.terrain.lacunarity);
+    out.put_f64(config.terrain.persistence);
+    out.put_f64(config.terrain.frequency);
+    out.put_f32(config.terrain.amplitude);
+    out.put_f32(config.terrain.ridge_mix);
+    out.put_f64(config.terrain.warp_strength);
+    out.put_u8(config.erosion.enabled as u8);
+    out.put_f32(config.erosion.rain);
+    out.put_f32(config.erosion.evaporation);
+    out.put_f32(config.erosion.flow_rate);
+    out.put_f32(config.erosion.capacity);
+    out.put_f32(config.erosion.deposition_rate);
+    out.put_f32(config.erosion.dissolve_rate);
+    out.put_f32(config.erosion.bedrock_rate);
+    out.put_f32(config.erosion.sea_level);
+    out.put_u8(config.thermal.enabled as u8);
+    for talus in [&config.thermal.sediment, &config.thermal.basalt, &config.thermal.granite] {
+        out.put_f32(talus.angle);
+        out.put_f32(talus.rate);
+    }
+    let orogeny = &config.orogeny;
+    out.put_u8(orogeny.enabled as u8);
+    for value in [
+        orogeny.granite_density,
+        orogeny.mantle_density,
+        orogeny.basalt_density,
+        orogeny.basalt_density_per_myr,
+        orogeny.trench_rate,
+        orogeny.arc_rate,
+        orogeny.collision_rate,
+        orogeny.arc_distance,
+        orogeny.max_trench_depth,
+        orogeny.max_elevation,
+    ] {
+        out.put_f32(value);
+    }
+    let spreading = &config.spreading;
+    out.put_u8(spreading.enabled as u8);
+    out.put_f32(spreading.subsidence);
+    out.put_f32(spreading.cooling_limit);
+    out.put_u8(spreading.rifting as u8);
+    out.put_f32(spreading.rift_area);
+    out.put_f32(spreading.rift_rate);
+    out.put_f32(spreading.rift_speed);
+    out.put_u8(config.lifecycle.suturing as u8);
+    out.put_f32(config.lifecycle.suture_length);
+    out.put_f32(config.lifecycle.suture_thickness);
+    out.put_f32(config.lifecycle.min_plate_area);
+    out.put_u8(config.isostasy.enabled as u8);
+    out.put_u8(isostasy_model_to_byte(config.isostasy.model));
+    out.put_f32(config.isostasy.sediment_density);
+    out.put_f32(config.isostasy.compensation_depth);
+    out.put_f32(config.isostasy.relaxation_time);
+    let flexure = &config.flexure;
+    out.put_u8(flexure.enabled as u8);
+    out.put_f32(flexure.continental_elastic_thickness);
+    out.put_f32(flexure.oceanic_elastic_thickness);
+    out.put_f32(flexure.youngs_modulus);
+    out.put_f32(flexure.poisson_ratio);
+    out.put_f32(flexure.tolerance);
+    out.put_u32(flexure.max_iterations);
+    let hotspots = &config.hotspots;
+    out.put_u8(hotspots.enabled as u8);
+    out.put_u32(hotspots.count);
+    out.put_f32(hotspots.spawn_rate);
+    out.put_f32(hotspots.strength);
+    out.put_f32(hotspots.radius);
+    out.put_f32(hotspots.lifetime);
+    out.put_f32(hotspots.max_height);
+    out.put_f32(hotspots.swell_age);
+    let forces = &config.forces;
+    out.put_u8(forces.enabled as u8);
+    out.put_f32(forces.slab_pull);
+    out.put_f32(forces.ridge_push);
+    out.put_f32(forces.basal_drag);
+    out.put_f32(forces.continental_drag);
+    out.put_f32(forces.response_time);
+    out.put_f32(forces.max_speed);
+
+    let clock = &data.clock;
+    out.put_u8(clock.running as u8);
+    out.put_f64(clock.time_myr);
+    out.put_u64(clock.ticks);
+    out.put_f32(clock.time_scale);
+
+    out.put_u32(data.plates.plates.len() as u32);
+    for plate in &data.plates.plates {
+        out.put_u32(plate.id);
+        out.put_f32(plate.pole.x);
+        out.put_f32(plate.pole.y);
+        out.put_f32(plate.pole.z);
+        out.put_f32(plate.angular_velocity);
+        out.put_f32(plate.pending_angle);
+        out.put_f32(plate.elastic_thickness);
+    }
+
+    let columns = &data.columns;
+    out.put_u32(columns.cell_count() as u32);
+    for layer in [
+        &columns.bedrock,
+        &columns.sediment,
+        &columns.water,
+        &columns.suspended_sediment,
+        &columns.crust_age,
+        &columns.crust_thickness,
+    ] {
+        let values: Vec<u32> = layer.iter().map(|value| value.to_bits()).collect();
+        put_planes(out, &values);
+    }
+    out.extend(columns.rock_type.iter().map(|rock| rock_to_byte(*rock)));
+    put_planes(out, &columns.plate_id);
+
+    out.put_u32(data.events.events.len() as u32);
+    for event in &data.events.events {
+        out.put_f64(event.time_myr);
+        out.put_u64(event.tick);
+        out.put_u8(event_kind_to_byte(event.kind));
+        out.put_u32(event.plate);
+        out.put_u32(event.other.unwrap_or(NO_PLATE));
+    }
+
+    out.put_u32(data.hotspots.len() as u32);
+    for hotspot in &data.hotspots {
+        out.put_f32(hotspot.position.x);
+        out.put_f32(hotspot.position.y);
+        out.put_f32(hotspot.position.z);
+        out.put_f32(hotspot.strength);
+        out.put_f32(hotspot.radius);
+        out.put_f32(hotspot.lifetime);
+        out.put_f64(hotspot.born_myr);
+        out.put_u64(hotspot.born_tick);
+    }
+}
+
+fn read_config(reader: &mut Reader) -> Result<WorldConfig, String> {
+    Ok(WorldConfig {
+        seed: reader.u64()?,
+        grid: grid_from_byte(reader.u8()?)?,
+        rows: reader.u32()?,
+        cols: reader.u32()?,
+        ico_subdivisions: reader.u32()?,
+        cube_face_cells: reader.u32()?,
+        timestep: reader.f64()?,
+        myr_per_tick: reader.f32()?,
+        oceanic_crust_height: reader.f32()?,
+        continental_crust_height: reader.f32()?,
+        oceanic_crust_thickness: reader.f32()?,
+        continental_crust_thickness: reader.f32()?,
+        plates: PlateGenSettings {
+            plate_count: reader.u32()? as usize,
+            size_variance: reader.f32()?,
+            continental_fraction: reader.f32()?,
+        },
+        terrain: TerrainSettings {
+            octaves: reader.u32()? as usize,
+            lacunarity: reader.f64()?,
+            persistence: reader.f64()?,
+            frequency: reader.f64()?,
+            amplitude: reader.f32()?,
+            ridge_mix: reader.f32()?,
+            warp_strength: reader.f64()?,
+        },
+        erosion: ErosionSettings {
+            enabled: reader.u8()? != 0,
+            rain: reader.f32()?,
+            evaporation: reader.f32()?,
+            flow_rate: reader.f32()?,
+            capacity: reader.f32()?,
+            deposition_rate: reader.f32()?,
+            dissolve_rate: reader.f32()?,
+            bedrock_rate: reader.f32()?,
+            sea_level: reader.f32()?,
+        },
+        thermal: ThermalSettings {
+            enabled: reader.u8()? != 0,
+            sediment: read_talus(reader)?,
+            basalt: read_talus(reader)?,
+            granite: read_talus(reader)?,
+        },
+        orogeny: OrogenySettings {
+            enabled: reader.u8()? != 0,
+            granite_density: reader.f32()?,
+            mantle_density: reader.f32()?,
+            basalt_density: reader.f32()?,
+            basalt_density_per_myr: reader.f32()?,
+            trench_rate: reader.f32()?,
+            arc_rate: reader.f32()?,
+            collision_rate: reader.f32()?,
+            arc_distance: reader.f32()?,
+            max_trench_depth: reader.f32()?,
+            max_elevation: reader.f32()?,
+        },
+        spreading: SpreadingSettings {
+            enabled: reader.u8()? != 0,
+            subsidence: reader.f32()?,
+            cooling_limit: reader.f32()?,
+            rifting: reader.u8()? != 0,
+            rift_area: reader.f32()?,
+            rift_rate: reader.f32()?,
+            rift_speed: reader.f32()?,
+        },
+        lifecycle: LifecycleSettings {
+            suturing: reader.u8()? != 0,
+            suture_length: reader.f32()?,
+            suture_thickness: reader.f32()?,
+            min_plate_area: reader.f32()?,
+        },
+        isostasy: IsostasySettings {
+            enabled: reader.u8()? != 0,
+            model: isostasy_model_from_byte(reader.u8()?)?,
+            sediment_density: reader.f32()?,
+            compensation_depth: reader.f32()?,
+            relaxation_time: reader.f32()?,
+        },
+        flexure: FlexureSettings {
+            enabled: reader.u8()? != 0,
+            continental_elastic_thickness: reader.f32()?,
+            oceanic_elastic_thickness: reader.f32()?,
+            youngs_modulus: reader.f32()?,
+            poisson_ratio: reader.f32()?,
+            tolerance: reader.f32()?,
+            max_iterations: reader.u32()?,
+        },
+        hotspots: HotspotSettings {
+            enabled: reader.u8()? != 0,
+            count: reader.u32()?,
+            spawn_rate: reader.f32()?,
+            strength: reader.f32()?,
+            radius: reader.f32()?,
+            lifetime: reader.f32()?,
+            max_height: reader.f32()?,
+            swell_age: reader.f32()?,
+        },
+        forces: PlateForceSettings {
+            enabled: reader.u8()? != 0,
+            slab_pull: reader.f32()?,
+            ridge_push: reader.f32()?,
+            basal_drag: reader.f32()?,
+            continental_drag: reader.f32()?,
+            response_time: reader.f32()?,
+            max_speed: reader.f32()?,
+        },
+    })
+}
+
+fn read_talus(reader: &mut Reader) -> Result<TalusSettings, String> {
+    Ok(TalusSettings { angle: reader.f32()?, rate: reader.f32()? })
+}
+
+fn read_clock(reader: &mut Reader) -> Result<SimulationClock, String> {
+    Ok(SimulationClock {
+        running: reader.u8()? != 0,
+        time_myr: reader.f64()?,
+        ticks: reader.u64()?,
+        time_scale: reader.f32()?,
+        step_requested: false,
+    })
+}
+
+fn read_plates(reader: &mut Reader) -> Result<Plates, String> {
+    let plate_count = reader.u32()? as usize;
+    let mut plates = Vec::new();
+    for _ in 0..plate_count {
+        plates.push(Plate {
+            id: reader.u32()?,
+            pole: Vec3::new(reader.f32()?, reader.f32()?, reader.f32()?),
+            angular_velocity: reader.f32()?,
+            pending_angle: reader.f32()?,
+            elastic_thickness: reader.f32()?,
+        });
+    }
+    Ok(Plates { plates })
+}
+
+fn read_events(reader: &mut Reader) -> Result<PlateEvents, String> {
+    let event_count = reader.u32()? as usize;
+    let mut events = Vec::new();
+    for _ in 0..event_count {
+        events.push(PlateEvent {
+            time_myr: reader.f64()?,
+            tick: reader.u64()?,
+            kind: event_kind_from_byte(reader.u8()?)?,
+            plate: reader.u32()?,
+            other: Some(reader.u32()?).filter(|&other| other != NO_PLATE),
+        });
+    }
+    Ok(PlateEvents { events })
+}
+
+fn read_hotspots(reader: &mut Reader) -> Result<Vec<Hotspot>, String> {
+    let hotspot_count = reader.u32()? as usize;
+    let mut hotspots = Vec::new();
+    for _ in 0..hotspot_count {
+        hotspots.push(Hotspot {
+            position: Vec3::new(reader.f32()?, reader.f32()?, reader.f32()?),
+            strength: reader.f32()?,
+            radius: reader.f32()?,
+            lifetime: reader.f32()?,
+            born_myr: reader.f64()?,
+            born_tick: reader.u64()?,
+        });
+    }
+    Ok(hotspots)
+}
+
+fn read_body_v1(reader: &mut Reader) -> Result<SaveData, String> {
+    let config = read_config(reader)?;
+    let clock = read_clock(reader)?;
+    let plates = read_plates(reader)?;
+
+    let cell_count = reader.u32()? as usize;
+    if cell_count != grid_cell_count(config.grid, config.grid_resolution()) {
+        return Err("the save's cells do not fit its grid".to_string());
+    }
+    let mut columns = Columns::default();
+    for layer in [
+        &mut columns.bedrock,
+        &mut columns.sediment,
+        &mut columns.water,
+        &mut columns.suspended_sediment,
+        &mut columns.crust_age,
+        &mut columns.crust_thickness,
+    ] {
+        *layer = reader.planes(cell_count)?.into_iter().map(f32::from_bits).collect();
+    }
+    columns.rock_type = reader.take(cell_count)?.iter().map(|byte| rock_from_byte(*byte)).collect::<Result<_, _>>()?;
+    columns.plate_id = reader.planes(cell_count)?;
+    let events = read_events(reader)?;
+    let hotspots = read_hotspots(reader)?;
+
+    Ok(SaveData { config, clock, plates, columns, events, hotspots })
+}
+
+// reads a body written by any format version and brings it up to the current SaveData
+fn read_body(header: &SaveHeader, reader: &mut Reader) -> Result<SaveData, String> {
+    match header.version {
+        1 => read_body_v1(reader),
+        version if version > FORMAT_VERSION => {
+            Err(format!("the save was made with a newer version of the app (format {})", version))
+        }
+        version => Err(format!("unknown save format {}", version)),
+    }
+}
+
+// a field of an object in a legacy save
+fn legacy_field<'a>(object: &'a serde_json::Value, field: &str) -> Result<&'a serde_json::Value, String> {
+    object.get(field).ok_or_else(|| format!("the save has no {}", field))
+}
+
+fn legacy_f64(object: &serde_json::Value, field: &str) -> Result<f64, String> {
+    legacy_field(object, field)?.as_f64().ok_or_else(|| format!("the save's {} is not a number", field))
+}
+
+fn legacy_u64(object: &serde_json::Value, field: &str) -> Result<u64, String> {
+    legacy_field(object, field)?.as_u64().ok_or_else(|| format!("the save's {} is not a whole number", field))
+}
+
+// the resource of a legacy save whose type path ends in `name`
+fn legacy_resource<'a>(json: &'a serde_json::Value, name: &str) -> Result<&'a serde_json::Value, String> {
+    let resources = legacy_field(json, "resources")?.as_object().ok_or("the save's resources are not a map")?;
+    let suffix = format!("::{}", name);
+    resources
+        .iter()
+        .find(|(type_path, _)| type_path.ends_with(&suffix))
+        .map(|(_, resource)| resource)
+        .ok_or_else(|| format!("the save has no {}", name))
+}
+
+// a layer of a legacy save, stored as `rows` rows of `cols` cells with the single pole cells repeated across their
+// rows, as one entry per cell of the lat/long grid
+fn legacy_cells<T>(
+    resource: &serde_json::Value,
+    layer: &str,
+    (rows, cols): (u32, u32),
+    cell: impl Fn(&serde_json::Value) -> Option<T>,
+) -> Result<Vec<T>, String> {
+    let bad_layer = || format!("the save's {} do not fit its grid", layer);
+    let saved_rows = legacy_field(resource, layer)?.as_array().ok_or_else(bad_layer)?;
+    if saved_rows.len() != rows as usize {
+        return Err(bad_layer());
+    }
+
+    let mut cells = Vec::new();
+    for (i, row) in saved_rows.iter().enumerate() {
+        let row = row.as_array().filter(|row| row.len() == cols as usize).ok_or_else(bad_layer)?;
+        let pole = i == 0 || i + 1 == saved_rows.len();
+        for value in row.iter().take(if pole { 1 } else { row.len() }) {
+            cells.push(cell(value).ok_or_else(bad_layer)?);
+        }
+    }
+    Ok(cells)
+}
+
+fn read_legacy_config(json: &serde_json::Value) -> Result<WorldConfig, String> {
+    let config = legacy_resource(json, "WorldConfig")?;
+    let plates = legacy_field(config, "plates")?;
+    let terrain = legacy_field(config, "terrain")?;
+    Ok(WorldConfig {
+        seed: legacy_u64(config, "seed")?,
+        grid: GridKind::LatLong,
+        rows: legacy_u64(config, "rows")? as u32,
+        cols: legacy_u64(config, "cols")? as u32,
+        timestep: legacy_f64(config, "timestep")?,
+        myr_per_tick: legacy_f64(config, "myr_per_tick")? as f32,
+        oceanic_crust_height: legacy_f64(config, "oceanic_crust_height")? as f32,
+        continental_crust_height: legacy_f64(config, "continental_crust_height")? as f32,
+        plates: PlateGenSettings {
+            plate_count: legacy_u64(plates, "plate_count")? as usize,
+            size_variance: legacy_f64(plates, "size_variance")? as f32,
+            continental_fraction: legacy_f64(plates, "continental_fraction")? as f32,
+        },
+        terrain: TerrainSettings {
+            octaves: legacy_u64(terrain, "octaves")? as usize,
+            lacunarity: legacy_f64(terrain, "lacunarity")?,
+            persistence: legacy_f64(terrain, "persistence")?,
+            frequency: legacy_f64(terrain, "frequency")?,
+            amplitude: legacy_f64(terrain, "amplitude")? as f32,
+            ridge_mix: legacy_f64(terrain, "ridge_mix")? as f32,
+            warp_strength: legacy_f64(terrain, "warp_strength")?,
+        },
+        ..default()
+    })
+}
+
+// version 0, a bevy_save JSON snapshot from before the binary format
+// its heights are bare bedrock and its crust has no age or thickness, so every cell is read in as crust that has just
+// formed at the saved height, and each plate gets the elastic thickness of its crust the way a new run sets it
+fn read_legacy(bytes: &[u8]) -> Result<SaveData, String> {
+    let json: serde_json::Value = serde_json::from_slice(bytes).map_err(|error| error.to_string())?;
+    let config = read_legacy_config(&json)?;
+    let size = (config.rows, config.cols);
+    if config.rows < 3 || config.cols == 0 {
+        return Err("the save's grid is too small".to_string());
+    }
+
+    let clock = legacy_resource(&json, "SimulationClock")?;
+    let clock = SimulationClock {
+        running: false,
+        time_myr: legacy_f64(clock, "time_myr")?,
+        ticks: legacy_u64(clock, "ticks")?,
+        time_scale: legacy_f64(clock, "time_scale")? as f32,
+        step_requested: false,
+    };
+
+    let saved_plates = legacy_field(legacy_resource(&json, "Plates")?, "plates")?;
+    let mut plates = Vec::new();
+    for plate in saved_plates.as_array().ok_or("the save's plates are not a list")? {
+        let pole = legacy_field(plate, "pole")?;
+        plates.push(Plate {
+            id: legacy_u64(plate, "id")? as u32,
+            pole: Vec3::new(
+                legacy_f64(pole, "x")? as f32,
+                legacy_f64(pole, "y")? as f32,
+                legacy_f64(pole, "z")? as f32,
+            ),
+            angular_velocity: legacy_f64(plate, "angular_velocity")? as f32,
+            pending_angle: legacy_f64(plate, "pending_angle")? as f32,
+            elastic_thickness: 0.,
+        });
+    }
+
+    let heights = legacy_cells(legacy_resource(&json, "HeightValues")?, "values", size, |value| value.as_f64())?;
+    let plate_map = legacy_resource(&json, "PlateMap")?;
+    let plate_ids = legacy_cells(plate_map, "plate_ids", size, |value| value.as_u64())?;
+    let rock_types = legacy_cells(plate_map, "crust", size, |value| match value.as_str()? {
+        "Oceanic" => Some(RockType::Basalt),
+        "Continental" => Some(RockType::Granite),
+        _ => None,
+    })?;
+
+    let cell_count = heights.len();
+    let mut columns = Columns::new(cell_count, RockType::Basalt, &config);
+    let mut continental = vec![false; plates.len()];
+    for cell in 0..cell_count {
+        let plate = plate_ids[cell] as usize;
+        if plate >= plates.len() {
+            return Err("the save's plate map names a plate it does not have".to_string());
+        }
+        columns.new_crust(cell, rock_types[cell], &config);
+        columns.bedrock[cell] = heights[cell] as f32;
+        columns.plate_id[cell] = plate as u32;
+        continental[plate] |= rock_types[cell] == RockType::Granite;
+    }
+    for (plate, continental) in plates.iter_mut().zip(continental) {
+        plate.elastic_thickness = config.flexure.elastic_thickness(continental);
+    }
+
+    Ok(SaveData {
+        config,
+        clock,
+        plates: Plates { plates },
+        columns,
+        events: PlateEvents::default(),
+        hotspots: Vec::new(),
+    })
+}
+
+pub fn encode(data: &SaveData) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    write_header(&mut out, &data.header());
+
+    let mut body = Vec::new();
+    write_body(&mut body, data);
+    let mut encoder = ZlibEncoder::new(out, Compression::default());
+    encoder.write_all(&body).map_err(|error| error.to_string())?;
+    encoder.finish().map_err(|error| error.to_string())
+}
+
+pub fn decode(bytes: &[u8]) -> Result<SaveData, String> {
+    if !bytes.starts_with(&MAGIC) && bytes.starts_with(b"{") {
+        return read_legacy(bytes);
+    }
+
+    let mut reader = Reader { bytes };
+    let header = read_header(&mut reader)?;
+
+    let mut body = Vec::new();
+    ZlibDecoder::new(reader.bytes).read_to_end(&mut body).map_err(|error| error.to_string())?;
+    let mut reader = Reader { bytes: &body };
+    let data = read_body(&header, &mut reader)?;
+
+    //the counts in a damaged body can still add up, anything left over or a header telling another story gives it away
+    if !reader.bytes.is_empty() {
+        return Err("the save file runs on past its end".to_string());
+    }
+    if data.config.grid != header.grid || data.config.grid_resolution() != header.resolution {
+        return Err("the save's header does not match its world".to_string());
+    }
+    Ok(data)
+}
+
+pub fn write_save(path: &Path, data: &SaveData) -> Result<(), String> {
+    if let Some(folder) = path.parent() {
+        std::fs::create_dir_all(folder).map_err(|error| error.to_string())?;
+    }
+    std::fs::write(path, encode(data)?).map_err(|error| error.to_string())
+}
+
+pub fn read_save(path: &Path) -> Result<SaveData, String> {
+    let bytes = std::fs::read(path).map_err(|error| error.to_string())?;
+    decode(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    // One save of each grid per format version, written once by the version it is named after and never rewritten.
+    // A format bump adds new ones, and the old ones keep checking that their reader brings them up to sample()
+    const FIXTURES: [(GridKind, &[u8]); 3] = [
+        (GridKind::LatLong, include_bytes!("../tests/fixtures/v1-latlong.tsim")),
+        (GridKind::Icosahedral, include_bytes!("../tests/fixtures/v1-ico.tsim")),
+        (GridKind::CubeSphere, include_bytes!("../tests/fixtures/v1-cube.tsim")),
+    ];
+
+    // a small world on the given grid, every value in it different enough that reading one field as another shows up
+    fn sample(grid: GridKind) -> SaveData {
+        let mut config = WorldConfig {
+            seed: 0x5eed_0001,
+            grid,
+            rows: 6,
+            cols: 8,
+            ico_subdivisions: 1,
+            cube_face_cells: 3,
+            myr_per_tick: 0.5,
+            ..default()
+        };
+        config.plates.plate_count = 3;
+        config.erosion.rain = 0.003;
+        config.thermal.granite.angle = 0.7;
+        config.orogeny.arc_distance = 0.09;
+        config.spreading.rifting = false;
+        config.lifecycle.min_plate_area = 0.02;
+        config.isostasy.model = IsostasyModel::Pratt;
+        config.flexure.max_iterations = 40;
+        config.hotspots.count = 2;
+        config.forces.max_speed = 0.03;
+
+        let cell_count = grid_cell_count(grid, config.grid_resolution());
+        let mut columns = Columns::new(cell_count, RockType::Basalt, &config);
+        for cell in 0..cell_count {
+            let x = cell as f32;
+            if cell % 3 == 0 {
+                columns.new_crust(cell, RockType::Granite, &config);
+            }
+            columns.bedrock[cell] += 0.001 * x;
+            columns.sediment[cell] = 0.0002 * x;
+            columns.water[cell] = 0.0003 * (cell % 5) as f32;
+            columns.suspended_sediment[cell] = 0.00001 * (cell % 7) as f32;
+            columns.crust_age[cell] = 2. * x;
+            columns.crust_thickness[cell] += 0.0005 * x;
+            columns.plate_id[cell] = (cell % 3) as u32;
+        }
+
+        let plates = (0..3)
+            .map(|id| Plate {
+                id,
+                pole: Vec3::new(id as f32, 1., -2.).normalize(),
+                angular_velocity: 0.01 * (id as f32 + 1.),
+                pending_angle: 0.001 * id as f32,
+                elastic_thickness: 20. + id as f32,
+            })
+            .collect();
+        let clock = SimulationClock {
+            running: false,
+            time_myr: 12.5,
+            ticks: 25,
+            time_scale: 2.,
+            step_requested: false,
+        };
+        let events = vec![
+            PlateEvent { time_myr: 3., tick: 6, kind: PlateEventKind::Split, plate: 0, other: Some(3) },
+            PlateEvent { time_myr: 9.5, tick: 19, kind: PlateEventKind::Destroy, plate: 3, other: None },
+        ];
+        let hotspots = vec![
+            Hotspot {
+                position: Vec3::Y,
+                strength: 0.1,
+                radius: 0.04,
+                lifetime: 150.,
+                born_myr: 0.,
+                born_tick: 0,
+            },
+            Hotspot {
+                position: Vec3::new(0.6, 0., 0.8),
+                strength: 0.05,
+                radius: 0.02,
+                lifetime: 80.,
+                born_myr: 7.5,
+                born_tick: 15,
+            },
+        ];
+
+        SaveData { config, clock, plates: Plates { plates }, columns, events: PlateEvents { events }, hotspots }
+    }
+
+    fn same(a: &dyn Reflect, b: &dyn Reflect) -> bool {
+        a.reflect_partial_eq(b) == Some(true)
+    }
+
+    fn assert_same(loaded: &SaveData, expected: &SaveData) {
+        assert!(same(&loaded.config, &expected.config), "config differs");
+        assert!(same(&loaded.clock, &expected.clock), "clock differs");
+        assert!(same(&loaded.plates, &expected.plates), "plates differ");
+        assert!(same(&loaded.columns, &expected.columns), "columns differ");
+        assert!(same(&loaded.events, &expected.events), "plate events differ");
+        assert!(same(&loaded.hotspots, &expected.hotspots), "hotspots differ");
+    }
+
+    #[test]
+    fn round_trip() {
+        for (grid, _) in FIXTURES {
+            let data = sample(grid);
+            assert_same(&decode(&encode(&data).unwrap()).unwrap(), &data);
+        }
+    }
+
+    #[test]
+    fn fixtures_load() {
+        for (grid, bytes) in FIXTURES {
+            let data = decode(bytes).unwrap_or_else(|error| panic!("{:?} fixture: {}", grid, error));
+            assert_same(&data, &sample(grid));
+        }
+    }
+
+    // a bevy_save JSON save written by the app before the binary format, 6x8 lat/long cells three ticks into a run
+    const LEGACY_FIXTURE: &[u8] = include_bytes!("../tests/fixtures/v0-latlong.json");
+
+    #[test]
+    fn legacy_fixture_migrates() {
+        let data = decode(LEGACY_FIXTURE).unwrap();
+        assert_eq!((data.config.seed, data.config.grid), (7, GridKind::LatLong));
+        assert_eq!((data.config.rows, data.config.cols), (6, 8));
+        assert_eq!(data.config.plates.plate_count, 3);
+        assert_eq!((data.clock.ticks, data.clock.time_myr), (3, 3.));
+        assert_eq!(data.plates.plates.len(), 3);
+        assert!(data.plates.plates.iter().all(|plate| plate.elastic_thickness > 0.));
+
+        //the pole rows count once, the first cell is the north pole and the last the south pole
+        let cell_count = grid_cell_count(GridKind::LatLong, data.config.grid_resolution());
+        assert_eq!(data.columns.cell_count(), cell_count);
+        assert_eq!(data.columns.bedrock[0], 0.9795594);
+        assert_eq!(data.columns.bedrock[1], 1.0205746);
+        assert_eq!(data.columns.bedrock[cell_count - 1], 1.0184773);
+        assert_eq!(data.columns.plate_id[0], 2);
+        assert!(data.columns.sediment.iter().chain(&data.columns.crust_age).all(|&value| value == 0.));
+        for cell in 0..cell_count {
+            let thickness = match data.columns.rock_type[cell] {
+                RockType::Basalt => data.config.oceanic_crust_thickness,
+                RockType::Granite => data.config.continental_crust_thickness,
+            };
+            assert_eq!(data.columns.crust_thickness[cell], thickness);
+        }
+
+        //once migrated it saves and loads as the current version
+        assert_same(&decode(&encode(&data).unwrap()).unwrap(), &data);
+    }
+
+    #[test]
+    fn damaged_legacy_saves_fail() {
+        for length in 0..LEGACY_FIXTURE.len() {
+            assert!(decode(&LEGACY_FIXTURE[..length]).is_err(), "cut to {} bytes", length);
+        }
+        let text = std::str::from_utf8(LEGACY_FIXTURE).unwrap();
+        //a layer that no longer fits the grid, an unknown crust type and a plate id that is not a number
+        let edits = [
+            ("\"rows\": 6", "\"rows\": 7"),
+            ("\"Continental\"", "\"Granite\""),
+            ("\"id\": 2", "\"id\": \"2\""),
+        ];
+        for (from, to) in edits {
+            assert!(text.contains(from));
+            assert!(decode(text.replacen(from, to, 1).as_bytes()).is_err(), "{} -> {}", from, to);
+        }
+    }
+
+    #[test]
+    fn newer_format_is_refused() {
+        let mut bytes = encode(&sample(GridKind::LatLong)).unwrap();
+        bytes[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert!(decode(&bytes).is_err_and(|error| error.contains("newer version")));
+    }
+
+    #[test]
+    fn truncated_saves_fail() {
+        for (_, bytes) in FIXTURES {
+            for length in 0..bytes.len() {
+                assert!(decode(&bytes[..length]).is_err(), "cut to {} of {} bytes", length, bytes.len());
+            }
+        }
+    }
+
+    #[test]
+    fn damaged_saves_fail() {
+        for (grid, bytes) in FIXTURES {
+            for index in 0..bytes.len() {
+                let mut damaged = bytes.to_vec();
+                damaged[index] ^= 0x5a;
+                //a few bits of a deflate stream make no difference to what it inflates to, those still load the same
+                if let Ok(data) = decode(&damaged) {
+                    assert_same(&data, &sample(grid));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn garbage_fails() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let header = &FIXTURES[0].1[..15];
+        for length in [0, 1, 4, 15, 16, 64, 1000, 100_000] {
+            let mut garbage = vec![0u8; length];
+            rng.fill(garbage.as_mut_slice());
+            assert!(decode(&garbage).is_err());
+            assert!(decode(&[header, &garbage].concat()).is_err());
+
+            //a well formed stream with nonsense in it
+            let mut encoder = ZlibEncoder::new(header.to_vec(), Compression::default());
+            encoder.write_all(&garbage).unwrap();
+            assert!(decode(&encoder.finish().unwrap()).is_err());
+        }
+    }
+
+    // writes the fixtures of the current format version, run once with --ignored after bumping it
+    #[test]
+    #[ignore]
+    fn write_fixtures() {
+        let names = [(GridKind::LatLong, "latlong"), (GridKind::Icosahedral, "ico"), (GridKind::CubeSphere, "cube")];
+        for (grid, name) in names {
+            let path = format!("tests/fixtures/v{}-{}.tsim", FORMAT_VERSION, name);
+            write_save(Path::new(&path), &sample(grid)).unwrap();
+        }
+    }
+}
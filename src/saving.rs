@@ -0,0 +1,112 @@
+// Saving simulations
+//
+// Pressing Save takes a snapshot of the simulation through SavePipeline on the main thread, then encodes and
+// writes it on the IO task pool so a large world never stalls a frame. A toast reports how it went. Exports of layers
+// and logs are written the same way, through ExportTask.
+
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use bevy::{
+    prelude::*,
+    tasks::{block_on, poll_once, IoTaskPool, Task},
+};
+use bevy_save::prelude::*;
+
+use crate::{
+    save_file::{write_save, SaveData, SAVE_EXTENSION},
+    settings::Settings,
+    toast::ShowToast,
+    SavePipeline,
+};
+
+// Sent by the save button
+#[derive(Event)]
+pub struct SaveRequest;
+
+// A save being written in the background, resolves to the path of the finished file
+#[derive(Component)]
+pub struct SaveTask(Task<Result<PathBuf, String>>);
+
+// An export being written in the background, resolves to the message to show once the file is written
+#[derive(Component)]
+pub struct ExportTask(Task<Result<String, String>>);
+
+impl ExportTask {
+    // runs the writing on the IO task pool, finish_exports reports how it went
+    pub fn spawn(commands: &mut Commands, write: impl FnOnce() -> Result<String, String> + Send + 'static) {
+        commands.spawn(Self(IoTaskPool::get().spawn(async move { write() })));
+    }
+}
+
+// file name (without extension) for a save made right now, milliseconds since the unix epoch keep them in order
+// two names asked for within the same millisecond get consecutive stamps so neither overwrites the other
+pub fn timestamped_name() -> String {
+    static LAST: AtomicU64 = AtomicU64::new(0);
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0);
+    let stamp = |last: u64| now.max(last + 1);
+    let last = LAST.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| Some(stamp(last))).unwrap_or(0);
+    format!("simulation-{}", stamp(last))
+}
+
+// takes the snapshot for every SaveRequest and hands the writing off to a background task
+pub fn start_save(world: &mut World) {
+    let requested = world.resource_mut::<Events<SaveRequest>>().drain().count() > 0;
+    if !requested {
+        return;
+    }
+
+    let file_name = format!("{}{}", timestamped_name(), SAVE_EXTENSION);
+    let path = world.resource::<Settings>().save_folder.join(file_name);
+    let snapshot = world.snapshot::<SavePipeline>();
+
+    let task = IoTaskPool::get().spawn(async move {
+        SaveData::from_snapshot(&snapshot)
+            .and_then(|data| write_save(&path, &data))
+            .map(|_| path)
+    });
+    world.spawn(SaveTask(task));
+}
+
+// checks on background saves and shows a toast when one finishes
+pub fn finish_saves(
+    mut commands: Commands,
+    mut task_query: Query<(Entity, &mut SaveTask)>,
+    mut toasts: EventWriter<ShowToast>,
+) {
+    for (entity, mut task) in &mut task_query {
+        let Some(result) = block_on(poll_once(&mut task.0)) else {
+            continue;
+        };
+
+        match result {
+            Ok(path) => toasts.send(ShowToast::info(format!("Saved to {}", path.display()))),
+            Err(error) => toasts.send(ShowToast::error(format!("Save failed: {}", error))),
+        };
+        commands.entity(entity).despawn();
+    }
+}
+
+// checks on background exports and shows a toast when one finishes
+pub fn finish_exports(
+    mut commands: Commands,
+    mut task_query: Query<(Entity, &mut ExportTask)>,
+    mut toasts: EventWriter<ShowToast>,
+) {
+    for (entity, mut task) in &mut task_query {
+        let Some(result) = block_on(poll_once(&mut task.0)) else {
+            continue;
+        };
+
+        match result {
+            Ok(message) => toasts.send(ShowToast::info(message)),
+            Err(error) => toasts.send(ShowToast::error(format!("Export failed: {}", error))),
+        };
+        commands.entity(entity).despawn();
+    }
+}
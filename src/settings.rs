@@ -0,0 +1,244 @@
+// User settings
+//
+// Settings that outlive a single run (the save folder, the world config of the last run and the seed to start new
+// runs from, how often and in how much memory step back keeps checkpoints, the window size and the key bindings) are
+// kept in a RON file in the platform config directory, ~/.config on Linux. The file is read at Startup and written
+// whenever the settings change. Anything wrong with it is shown as a toast instead of stopping the app, and the
+// defaults are used in its place.
+
+use std::{
+    any::TypeId,
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    app::AppExit,
+    prelude::*,
+    reflect::{
+        serde::{TypedReflectDeserializer, TypedReflectSerializer},
+        TypeRegistry,
+    },
+    window::{PrimaryWindow, WindowResized},
+};
+use bevy_save::prelude::*;
+use platform_dirs::AppDirs;
+use serde::de::DeserializeSeed;
+
+use crate::{config::WorldConfig, toast::ShowToast};
+
+const SETTINGS_FILE: &str = "settings.ron";
+
+// Keys for the simulation controls, keys missing from an older settings file keep their defaults
+#[derive(Reflect, Clone, Debug)]
+#[reflect(Default)]
+pub struct Keybindings {
+    pub pause: KeyCode,
+    pub step: KeyCode,
+    pub speed_up: KeyCode,
+    pub slow_down: KeyCode,
+    pub step_back: KeyCode,
+
+    // spin the globe while held
+    pub rotate_x: KeyCode,
+    pub rotate_z: KeyCode,
+
+    // show the next column layer on the globe, and write the one shown to the save folder
+    pub cycle_layer: KeyCode,
+    pub export_layer: KeyCode,
+
+    // show or hide the plate boundary lines
+    pub toggle_boundaries: KeyCode,
+
+    // write the plate lifecycle log to the save folder
+    pub export_events: KeyCode,
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Self {
+            pause: KeyCode::Space,
+            step: KeyCode::Period,
+            speed_up: KeyCode::BracketRight,
+            slow_down: KeyCode::BracketLeft,
+            step_back: KeyCode::Comma,
+            rotate_x: KeyCode::KeyX,
+            rotate_z: KeyCode::KeyZ,
+            cycle_layer: KeyCode::KeyL,
+            export_layer: KeyCode::KeyE,
+            toggle_boundaries: KeyCode::KeyB,
+            export_events: KeyCode::KeyJ,
+        }
+    }
+}
+
+// fields missing from an older settings file keep their defaults
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource, Default)]
+pub struct Settings {
+    // folder new saves are written to and the file browser lists
+    pub save_folder: PathBuf,
+
+    // config of the last run, new runs start from it
+    pub world: WorldConfig,
+
+    // seed new runs start from, set it to the seed of a recorded run to replay it, without one every run gets a fresh
+    // seed
+    pub seed: Option<u64>,
+
+    // ticks between step back checkpoints, and the megabytes of memory the step back history may hold
+    pub history_interval: u64,
+    pub history_memory_mb: usize,
+
+    // logical size of the window when the app was last closed
+    pub window_width: f32,
+    pub window_height: f32,
+
+    pub keys: Keybindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            save_folder: SAVE_DIR.clone(),
+            world: WorldConfig::default(),
+            seed: None,
+            history_interval: 10,
+            history_memory_mb: 64,
+            window_width: 1280.,
+            window_height: 720.,
+            keys: Keybindings::default(),
+        }
+    }
+}
+
+// where the settings file lives, None if the platform has no config directory
+fn settings_path() -> Option<PathBuf> {
+    AppDirs::new(Some(WORKSPACE), true).map(|dirs| dirs.config_dir.join(SETTINGS_FILE))
+}
+
+fn read_settings(path: &Path, registry: &TypeRegistry) -> Result<Settings, String> {
+    let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+    let registration = registry.get(TypeId::of::<Settings>()).ok_or("settings type is not registered")?;
+
+    let mut deserializer = ron::de::Deserializer::from_str(&text).map_err(|error| error.to_string())?;
+    let reflected = TypedReflectDeserializer::new(registration, registry)
+        .deserialize(&mut deserializer)
+        .map_err(|error| error.to_string())?;
+
+    Settings::from_reflect(reflected.as_ref()).ok_or_else(|| "the file does not hold settings".to_string())
+}
+
+fn write_settings(path: &Path, settings: &Settings, registry: &TypeRegistry) -> Result<(), String> {
+    let text = ron::ser::to_string_pretty(
+        &TypedReflectSerializer::new(settings, registry),
+        ron::ser::PrettyConfig::default(),
+    )
+    .map_err(|error| error.to_string())?;
+
+    if let Some(folder) = path.parent() {
+        std::fs::create_dir_all(folder).map_err(|error| error.to_string())?;
+    }
+    std::fs::write(path, text).map_err(|error| error.to_string())
+}
+
+// checks that saves can be written to a folder by writing and removing a small file in it
+pub fn validate_save_folder(folder: &Path) -> Result<(), String> {
+    if !folder.is_dir() {
+        return Err(format!("{} does not exist", folder.display()));
+    }
+
+    let probe = folder.join(".write-test");
+    std::fs::write(&probe, b"")
+        .and_then(|_| std::fs::remove_file(&probe))
+        .map_err(|error| format!("{} is not writable ({})", folder.display(), error))
+}
+
+// reads the settings file, sizes the window and checks the save folder
+pub fn settings_setup(
+    mut commands: Commands,
+    registry: Res<AppTypeRegistry>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+    mut toasts: EventWriter<ShowToast>,
+) {
+    let settings = match settings_path() {
+        Some(path) if path.exists() => match read_settings(&path, &registry.read()) {
+            Ok(settings) => settings,
+            Err(error) => {
+                toasts.send(ShowToast::error(format!("Could not read {}, using default settings: {}", path.display(), error)));
+                Settings::default()
+            }
+        },
+        //first start, the file gets written once write_changed_settings sees the new resource
+        Some(_) => Settings::default(),
+        None => {
+            toasts.send(ShowToast::error("No config folder found, settings will not be kept"));
+            Settings::default()
+        }
+    };
+
+    for mut window in &mut window_query {
+        window.resolution.set(settings.window_width, settings.window_height);
+    }
+
+    //the default folder is ours to create, a folder the user picked is only checked
+    if settings.save_folder == *SAVE_DIR {
+        let _ = std::fs::create_dir_all(&settings.save_folder);
+    }
+    if let Err(error) = validate_save_folder(&settings.save_folder) {
+        toasts.send(ShowToast::error(format!("Save folder {}, choose another one before saving", error)));
+    }
+
+    commands.insert_resource(settings.world.clone());
+    commands.insert_resource(settings);
+}
+
+// copies the last used world config and the window size into the settings
+pub fn track_settings(
+    mut settings: ResMut<Settings>,
+    config: Res<WorldConfig>,
+    mut resized: EventReader<WindowResized>,
+    window_query: Query<Entity, With<PrimaryWindow>>,
+) {
+    if config.is_changed() {
+        settings.world = config.clone();
+    }
+
+    //resizing sends an event every frame while dragging, the size is only written out when the app closes
+    for event in resized.read() {
+        if window_query.contains(event.window) {
+            let settings = settings.bypass_change_detection();
+            settings.window_width = event.width;
+            settings.window_height = event.height;
+        }
+    }
+}
+
+// writes the settings file whenever the settings change
+pub fn write_changed_settings(
+    settings: Res<Settings>,
+    registry: Res<AppTypeRegistry>,
+    mut toasts: EventWriter<ShowToast>,
+) {
+    let Some(path) = settings_path() else {
+        return;
+    };
+    if let Err(error) = write_settings(&path, &settings, &registry.read()) {
+        toasts.send(ShowToast::error(format!("Could not write {}: {}", path.display(), error)));
+    }
+}
+
+// writes the settings file one last time so the window size is kept
+pub fn write_settings_on_exit(
+    mut exit: EventReader<AppExit>,
+    settings: Res<Settings>,
+    registry: Res<AppTypeRegistry>,
+) {
+    if exit.read().count() == 0 {
+        return;
+    }
+    if let Some(path) = settings_path() {
+        if let Err(error) = write_settings(&path, &settings, &registry.read()) {
+            error!("could not write {}: {}", path.display(), error);
+        }
+    }
+}
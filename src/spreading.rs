@@ -0,0 +1,267 @@
+// Seafloor spreading and rifting
+//
+// Where plates pull apart, advance_plates fills the gap with new oceanic crust, at the height of a mid-ocean ridge
+// and with an age of zero. As that crust moves away from the ridge it cools, shrinks and sinks. The depth follows the
+// half-space cooling curve, the floor sinks with the square root of its age, until about 80 million years when the
+// curve flattens out and old ocean floor stops getting deeper.
+//
+// Large continents trap the mantle's heat under them, and the swelling puts them under tension until they tear apart.
+// Every tick each continental plate has a chance to rift that grows with how much continent it carries. A rift cuts
+// the plate in two along a great circle through the middle of its continent, and the halves start drifting apart.
+// New ocean floor then opens up between them, the way the Atlantic opened.
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    clock::SimulationClock,
+    columns::{Columns, RockType},
+    config::{WorldConfig, RIFT_STREAM},
+    grid::Grid,
+    lifecycle::{cells_left_of_path, split_plate, PlateEvents},
+    plates::{random_unit_vector, Plates},
+};
+
+// Settings for ocean floor cooling and continental rifting, part of WorldConfig
+#[derive(Reflect, Clone, Debug)]
+pub struct SpreadingSettings {
+    pub enabled: bool,
+
+    // how far ocean floor sinks below the ridge for the square root of its age in million years, about 350 m on
+    // Earth, and the age it stops sinking at
+    pub subsidence: f32,
+    pub cooling_limit: f32,
+
+    pub rifting: bool,
+
+    // share of the globe's surface a plate's continent has to cover before it starts to rift, and the chance of a
+    // rift every million years once it covers twice that
+    pub rift_area: f32,
+    pub rift_rate: f32,
+
+    // radians per million years the two halves of a rifted plate pull apart at
+    pub rift_speed: f32,
+}
+
+impl Default for SpreadingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            subsidence: 0.0028,
+            cooling_limit: 80.,
+            rifting: true,
+            rift_area: 0.1,
+            rift_rate: 0.01,
+            rift_speed: 0.02,
+        }
+    }
+}
+
+impl SpreadingSettings {
+    // depth below the ridge of ocean floor of an age
+    pub fn cooling_depth(&self, age: f32) -> f32 {
+        self.subsidence * age.clamp(0., self.cooling_limit).sqrt()
+    }
+}
+
+// sinks the ocean floor along the cooling curve, run on the fixed timestep after the crust has been aged
+// only the change since the last tick is applied, so whatever else raised or lowered the floor is kept
+// with isostasy on, the cooling is part of the height the floor settles to and this stays out of the way
+pub fn cool_ocean_floor(
+    config: Res<WorldConfig>,
+    grid: Res<Grid>,
+    mut columns: ResMut<Columns>,
+) {
+    let settings = &config.spreading;
+    if !settings.enabled || config.isostasy.enabled || !columns.fits(&grid) {
+        return;
+    }
+
+    let Columns { bedrock, crust_age, rock_type, .. } = &mut *columns;
+    for cell in 0..bedrock.len() {
+        if rock_type[cell] == RockType::Basalt {
+            let age = crust_age[cell];
+            bedrock[cell] -= settings.cooling_depth(age) - settings.cooling_depth(age - config.myr_per_tick);
+        }
+    }
+}
+
+// gives every plate carrying enough continent its chance to rift, run on the fixed timestep
+#[allow(clippy::too_many_arguments)]
+pub fn rift_continents(
+    config: Res<WorldConfig>,
+    clock: Res<SimulationClock>,
+    grid: Res<Grid>,
+    mut columns: ResMut<Columns>,
+    mut plates: ResMut<Plates>,
+    mut events: ResMut<PlateEvents>,
+) {
+    let settings = &config.spreading;
+    if !settings.enabled || !settings.rifting || !columns.fits(&grid) {
+        return;
+    }
+
+    //area and area weighted center of each plate's continent
+    let plate_count = plates.plates.len();
+    let mut areas = vec![0f32; plate_count];
+    let mut centers = vec![Vec3::ZERO; plate_count];
+    let mut total_area = 0.;
+    for cell in 0..grid.cell_count() {
+        let area = grid.cell_area(cell);
+        total_area += area;
+        let plate = columns.plate_id[cell] as usize;
+        if plate < plate_count && columns.rock_type[cell] == RockType::Granite {
+            areas[plate] += area;
+            centers[plate] += grid.direction(cell) * area;
+        }
+    }
+
+    let mut rng = config.tick_rng(RIFT_STREAM, clock.ticks);
+    for plate in 0..plate_count {
+        let tension = (areas[plate] / total_area / settings.rift_area - 1.).max(0.);
+        let chance = settings.rift_rate * config.myr_per_tick * tension;
+        if chance <= 0. || !rng.gen_bool(chance.min(1.) as f64) {
+            continue;
+        }
+        let Some(center) = centers[plate].try_normalize() else {
+            continue;
+        };
+
+        //cut along a random great circle through the middle of the continent, the side the normal points to goes to the
+        //new plate
+        let normal = random_unit_vector(&mut rng).reject_from_normalized(center).normalize_or_zero();
+        if normal == Vec3::ZERO {
+            continue;
+        }
+        let along = normal.cross(center) / 2.;
+        let path = [(center - along).normalize(), (center + along).normalize()];
+        let split = cells_left_of_path(&grid, &columns, plate as u32, &path);
+        let split_area: f32 = split
+            .iter()
+            .filter(|&&cell| columns.rock_type[cell] == RockType::Granite)
+            .map(|&cell| grid.cell_area(cell))
+            .sum();
+
+        //a cut that only shaves a sliver off the continent is not a rift
+        let share = split_area / areas[plate];
+        if !(0.25..=0.75).contains(&share) {
+            continue;
+        }
+
+        //spin the halves apart about the axis that moves the middle of the continent straight across the rift
+        let axis = center.cross(normal) * settings.rift_speed / 2.;
+        let new_plate = split_plate(&mut plates, &mut columns, &mut events, &clock, plate as u32, &split);
+        let rotation = plates.plates[plate].rotation();
+        plates.plates[plate].set_rotation(rotation - axis);
+        plates.plates[new_plate as usize].set_rotation(rotation + axis);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::{
+        grid::GridKind,
+        plates::{advance_plates, Plate},
+    };
+
+    // two plates of 100 Myr old ocean floor meeting along the meridian x = 0 and pulling apart at +z
+    fn opening_ocean() -> World {
+        let mut config = WorldConfig { grid: GridKind::CubeSphere, cube_face_cells: 16, ..default() };
+        config.isostasy.enabled = false;
+        let grid = Grid::from_config(&config);
+        let mut columns = Columns::new(grid.cell_count(), RockType::Basalt, &config);
+        for cell in 0..grid.cell_count() {
+            columns.crust_age[cell] = 100.;
+            columns.bedrock[cell] -= config.spreading.cooling_depth(100.);
+            columns.plate_id[cell] = if grid.direction(cell).x > 0. { 0 } else { 1 };
+        }
+        let plates = vec![
+            Plate { id: 0, pole: Vec3::Y, angular_velocity: 0.01, ..default() },
+            Plate { id: 1, pole: Vec3::Y, angular_velocity: -0.01, ..default() },
+        ];
+
+        let mut world = World::new();
+        world.insert_resource(config);
+        world.insert_resource(grid);
+        world.insert_resource(columns);
+        world.insert_resource(Plates { plates });
+        world
+    }
+
+    fn tick(world: &mut World) {
+        world.run_system_once(advance_plates);
+        world.run_system_once(cool_ocean_floor);
+    }
+
+    #[test]
+    fn new_floor_forms_at_the_ridge_and_sinks_as_it_cools() {
+        let mut world = opening_ocean();
+        //the plates only move once they have turned far enough to land on other cells
+        for _ in 0..20 {
+            tick(&mut world);
+            if world.resource::<Columns>().crust_age.iter().any(|&age| age < 100.) {
+                break;
+            }
+        }
+        let config = world.resource::<WorldConfig>().clone();
+        let (grid, columns) = (world.resource::<Grid>(), world.resource::<Columns>());
+        let ridge: Vec<usize> = (0..grid.cell_count()).filter(|&cell| columns.crust_age[cell] < 100.).collect();
+        assert!(!ridge.is_empty(), "the plates opened no gap");
+        for &cell in &ridge {
+            assert!(grid.direction(cell).z > 0. && grid.direction(cell).x.abs() < 0.2, "new floor at {}", cell);
+            assert_eq!(columns.crust_age[cell], 0.);
+            assert_eq!(columns.bedrock[cell], config.oceanic_crust_height);
+        }
+
+        //the floor moves off the ridge getting older and deeper, resampling onto the grid mixes crust of neighboring
+        //ages so it stays a little above the curve but never sinks below it
+        for _ in 0..40 {
+            tick(&mut world);
+        }
+        let (grid, columns) = (world.resource::<Grid>(), world.resource::<Columns>());
+        for side in [1., -1.] {
+            let mut row: Vec<usize> = (0..grid.cell_count())
+                .filter(|&cell| {
+                    let dir = grid.direction(cell);
+                    dir.z > 0. && dir.x * side >= 0. && dir.y > 0. && dir.y < 0.1 && columns.crust_age[cell] < 100.
+                })
+                .collect();
+            row.sort_by(|&a, &b| (grid.direction(a).x * side).total_cmp(&(grid.direction(b).x * side)));
+            assert!(row.len() >= 4, "only {} cells of new floor", row.len());
+            for pair in row.windows(2) {
+                assert!(columns.crust_age[pair[1]] > columns.crust_age[pair[0]]);
+                assert!(columns.bedrock[pair[1]] < columns.bedrock[pair[0]]);
+            }
+            for &cell in &row {
+                let depth = config.oceanic_crust_height - columns.bedrock[cell];
+                let curve = config.spreading.cooling_depth(columns.crust_age[cell]);
+                let age = columns.crust_age[cell];
+                assert!(depth <= curve + 1e-6 && depth >= curve / 2., "{} deep at {} Myr", depth, age);
+            }
+        }
+    }
+
+    #[test]
+    fn still_floor_follows_the_cooling_curve() {
+        let mut world = opening_ocean();
+        let config = world.resource::<WorldConfig>().clone();
+        let fresh = Columns::new(world.resource::<Grid>().cell_count(), RockType::Basalt, &config);
+        world.resource_mut::<Columns>().clone_from(&fresh);
+        world.resource_mut::<Plates>().plates.iter_mut().for_each(|plate| plate.angular_velocity = 0.);
+
+        for ticks in 1..=120 {
+            tick(&mut world);
+            //past the cooling limit the floor stops sinking
+            let columns = world.resource::<Columns>();
+            let age = ticks as f32 * config.myr_per_tick;
+            let expected = config.oceanic_crust_height - config.spreading.cooling_depth(age);
+            assert!(columns.crust_age.iter().all(|&cell_age| cell_age == age));
+            for &height in &columns.bedrock {
+                assert!((height - expected).abs() < 1e-5, "{} at {} Myr, {} expected", height, age, expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+// Initial terrain
+//
+// Heights are perturbed with fractal noise sampled at each vertex's position on the unit sphere rather than at its
+// place in the grid, so there is no seam where the columns wrap around and no pinching at the poles.
+
+use bevy::prelude::*;
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin, RidgedMulti};
+use rand::RngCore;
+
+use crate::{
+    columns::Columns,
+    config::{WorldConfig, TERRAIN_STREAM},
+    grid::Grid,
+};
+
+// Settings for the noise added on top of the base heights when a globe is created, part of WorldConfig
+#[derive(Reflect, Clone, Debug)]
+pub struct TerrainSettings {
+    // number of noise layers added together, each one finer than the last
+    pub octaves: usize,
+
+    // how much the frequency grows from one octave to the next
+    pub lacunarity: f64,
+
+    // how much the strength shrinks from one octave to the next
+    pub persistence: f64,
+
+    // frequency of the first octave, in features per globe radius
+    pub frequency: f64,
+
+    // largest height change the noise can make, relative to a globe radius of 1
+    pub amplitude: f32,
+
+    // 0 is pure fBm (rolling hills), 1 is pure ridged multifractal (sharp mountain ridges)
+    pub ridge_mix: f32,
+
+    // how far the sample position is pushed around by another noise field before sampling, 0 turns warping off
+    pub warp_strength: f64,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        Self {
+            octaves: 6,
+            lacunarity: 2.0,
+            persistence: 0.5,
+            frequency: 1.5,
+            amplitude: 0.02,
+            ridge_mix: 0.3,
+            warp_strength: 0.4,
+        }
+    }
+}
+
+// The noise functions built from a set of TerrainSettings
+pub struct TerrainNoise {
+    fbm: Fbm<Perlin>,
+    ridged: RidgedMulti<Perlin>,
+    warp: [Fbm<Perlin>; 3],
+    settings: TerrainSettings,
+}
+
+impl TerrainNoise {
+    pub fn new(settings: &TerrainSettings, seed: u32) -> Self {
+        let octaves = settings.octaves.clamp(1, Fbm::<Perlin>::MAX_OCTAVES);
+
+        //every layer gets its own seed so the warp doesn't line up with the terrain it is warping
+        let make_fbm = |seed: u32| {
+            Fbm::<Perlin>::new(seed)
+                .set_octaves(octaves)
+                .set_frequency(settings.frequency)
+                .set_lacunarity(settings.lacunarity)
+                .set_persistence(settings.persistence)
+        };
+
+        Self {
+            fbm: make_fbm(seed),
+            ridged: RidgedMulti::<Perlin>::new(seed.wrapping_add(1))
+                .set_octaves(octaves)
+                .set_frequency(settings.frequency)
+                .set_lacunarity(settings.lacunarity)
+                .set_persistence(settings.persistence),
+            warp: [
+                make_fbm(seed.wrapping_add(2)),
+                make_fbm(seed.wrapping_add(3)),
+                make_fbm(seed.wrapping_add(4)),
+            ],
+            settings: settings.clone(),
+        }
+    }
+
+    // height offset for a point on the unit sphere, between -amplitude and amplitude
+    pub fn sample(&self, dir: Vec3) -> f32 {
+        let mut point = [dir.x as f64, dir.y as f64, dir.z as f64];
+
+        //domain warping, offset the point by a vector of noise before sampling
+        if self.settings.warp_strength != 0. {
+            let offset = [self.warp[0].get(point), self.warp[1].get(point), self.warp[2].get(point)];
+            for (coord, offset) in point.iter_mut().zip(offset) {
+                *coord += self.settings.warp_strength * offset;
+            }
+        }
+
+        let mix = self.settings.ridge_mix.clamp(0., 1.) as f64;
+        let value = (1. - mix) * self.fbm.get(point) + mix * self.ridged.get(point);
+
+        self.settings.amplitude * value.clamp(-1., 1.) as f32
+    }
+}
+
+// adds fractal noise to the bedrock of every cell of the globe
+pub fn terrain_setup(
+    config: Res<WorldConfig>,
+    grid: Res<Grid>,
+    mut columns: ResMut<Columns>,
+) {
+    let terrain = TerrainNoise::new(&config.terrain, config.rng(TERRAIN_STREAM).next_u32());
+
+    for (cell, bedrock) in columns.bedrock.iter_mut().enumerate() {
+        *bedrock += terrain.sample(grid.direction(cell));
+    }
+}
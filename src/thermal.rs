@@ -0,0 +1,245 @@
+// Thermal erosion
+//
+// Weathered material on a slope steeper than its angle of repose slides down to the neighbors below it, which wears
+// the sharp steps left by plates crashing together into talus slopes. Loose sediment slides first and at a gentle
+// angle. Bedrock only breaks off where the slope is steeper than its own rock type allows, and what breaks off lands
+// below as sediment, so cliffs end up with scree at their feet.
+//
+// Angles are measured on the globe as it is drawn, with the heights exaggerated. Material is moved as volume like in
+// hydraulic erosion, so nothing is lost where cells differ in size, and every cell's moves are worked out from the
+// heights at the start of the tick so the order cells are visited in does not matter.
+
+use bevy::prelude::*;
+
+use crate::{
+    columns::{Columns, RockType},
+    config::WorldConfig,
+    grid::Grid,
+};
+
+// How one material behaves on a slope
+#[derive(Reflect, Clone, Debug)]
+pub struct TalusSettings {
+    // steepest slope the material stays on, in degrees
+    pub angle: f32,
+
+    // fraction of the excess height above that slope that slides off each tick
+    pub rate: f32,
+}
+
+// Settings for thermal erosion, part of WorldConfig
+#[derive(Reflect, Clone, Debug)]
+pub struct ThermalSettings {
+    pub enabled: bool,
+    pub sediment: TalusSettings,
+    pub basalt: TalusSettings,
+    pub granite: TalusSettings,
+}
+
+impl Default for ThermalSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sediment: TalusSettings { angle: 25., rate: 0.3 },
+            basalt: TalusSettings { angle: 35., rate: 0.05 },
+            granite: TalusSettings { angle: 40., rate: 0.05 },
+        }
+    }
+}
+
+impl ThermalSettings {
+    pub fn rock(&self, rock_type: RockType) -> &TalusSettings {
+        match rock_type {
+            RockType::Basalt => &self.basalt,
+            RockType::Granite => &self.granite,
+        }
+    }
+}
+
+// fills `excesses` with how far a cell's ground stands above each neighbor beyond what a material's angle of repose
+// allows, zero for neighbors it does not lean over, and returns the height of material that slides off, at most
+// `available`
+// `drops` holds the drop in ground height to each neighbor and the angle between the two cells
+fn excess_heights(talus: &TalusSettings, available: f32, drops: &[(f32, f32)], excesses: &mut Vec<f32>) -> f32 {
+    let max_slope = talus.angle.to_radians().tan();
+    excesses.clear();
+    excesses.extend(drops.iter().map(|(drop, distance)| (drop - max_slope * distance).max(0.)));
+
+    //half the largest excess levels the steepest pair of cells, anything more would tip the slope the other way
+    let largest = excesses.iter().copied().fold(0f32, f32::max);
+    (largest / 2. * talus.rate).min(available)
+}
+
+// one tick of slumping, run on the fixed timestep after hydraulic erosion
+pub fn thermal_erode(
+    config: Res<WorldConfig>,
+    grid: Res<Grid>,
+    mut columns: ResMut<Columns>,
+) {
+    let settings = &config.thermal;
+    if !settings.enabled || !columns.fits(&grid) {
+        return;
+    }
+
+    let cell_count = grid.cell_count();
+    let heights = columns.heights();
+    let mut sediment_change = vec![0f32; cell_count];
+    let mut bedrock_loss = vec![0f32; cell_count];
+    let mut drops = Vec::new();
+    let mut excesses = Vec::new();
+
+    for cell in 0..cell_count {
+        let dir = grid.direction(cell);
+        let neighbors = grid.neighbors(cell);
+        drops.clear();
+        drops.extend(
+            neighbors
+                .iter()
+                .map(|&neighbor| (heights[cell] - heights[neighbor as usize], dir.angle_between(grid.direction(neighbor as usize)))),
+        );
+        let area = grid.cell_area(cell);
+
+        //loose sediment first, then bedrock broken off by the steeper slope its rock type stands at
+        let layers = [
+            (&settings.sediment, columns.sediment[cell], false),
+            (settings.rock(columns.rock_type[cell]), columns.crust_thickness[cell], true),
+        ];
+        for (talus, available, is_bedrock) in layers {
+            let moved = excess_heights(talus, available, &drops, &mut excesses);
+            let total: f32 = excesses.iter().sum();
+            if moved <= 0. || total <= 0. {
+                continue;
+            }
+
+            if is_bedrock {
+                bedrock_loss[cell] += moved;
+            } else {
+                sediment_change[cell] -= moved;
+            }
+            for (&neighbor, excess) in neighbors.iter().zip(&excesses) {
+                let neighbor = neighbor as usize;
+                sediment_change[neighbor] += moved * area * excess / total / grid.cell_area(neighbor);
+            }
+        }
+    }
+
+    let Columns { bedrock, sediment, crust_thickness, .. } = &mut *columns;
+    for cell in 0..cell_count {
+        bedrock[cell] -= bedrock_loss[cell];
+        crust_thickness[cell] = (crust_thickness[cell] - bedrock_loss[cell]).max(0.);
+        sediment[cell] = (sediment[cell] + sediment_change[cell]).max(0.);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::grid::GridKind;
+
+    // a small icosahedral globe of one rock type with no sediment on it, and the cell facing +z
+    fn flat_world(rock_type: RockType) -> (WorldConfig, Grid, Columns, usize) {
+        let config = WorldConfig { grid: GridKind::Icosahedral, ico_subdivisions: 3, ..default() };
+        let grid = Grid::from_config(&config);
+        let columns = Columns::new(grid.cell_count(), rock_type, &config);
+        let peak = grid.cell_from_direction(Vec3::Z);
+        (config, grid, columns, peak)
+    }
+
+    // the columns after `ticks` ticks of slumping
+    fn slump(config: &WorldConfig, columns: &Columns, ticks: usize) -> Columns {
+        let mut world = World::new();
+        world.insert_resource(config.clone());
+        world.insert_resource(Grid::from_config(config));
+        world.insert_resource(columns.clone());
+        for _ in 0..ticks {
+            world.run_system_once(thermal_erode);
+        }
+        world.remove_resource::<Columns>().unwrap()
+    }
+
+    // raises a cell so it stands over its nearest neighbor at the given slope, in degrees
+    fn raise(grid: &Grid, columns: &mut Columns, cell: usize, degrees: f32) {
+        let dir = grid.direction(cell);
+        let neighbors = grid.neighbors(cell).iter().map(|&neighbor| grid.direction(neighbor as usize));
+        let nearest = neighbors.map(|neighbor| dir.angle_between(neighbor)).fold(f32::MAX, f32::min);
+        let rise = degrees.to_radians().tan() * nearest;
+        columns.bedrock[cell] += rise;
+        columns.crust_thickness[cell] += rise;
+    }
+
+    #[test]
+    fn steep_column_slumps_onto_its_lower_neighbors() {
+        let (config, grid, mut columns, peak) = flat_world(RockType::Granite);
+        raise(&grid, &mut columns, peak, 70.);
+        let after = slump(&config, &columns, 1);
+
+        assert!(after.bedrock[peak] < columns.bedrock[peak]);
+        assert!(after.crust_thickness[peak] < columns.crust_thickness[peak]);
+        for &neighbor in grid.neighbors(peak) {
+            assert!(after.sediment[neighbor as usize] > 0., "nothing landed on {}", neighbor);
+        }
+        let near = |cell: usize| cell == peak || grid.neighbors(peak).contains(&(cell as u32));
+        for cell in (0..grid.cell_count()).filter(|&cell| !near(cell)) {
+            assert_eq!(after.heights()[cell], columns.heights()[cell], "cell {} is not next to the peak", cell);
+        }
+    }
+
+    #[test]
+    fn gentle_column_stays_put() {
+        let (config, grid, mut columns, peak) = flat_world(RockType::Granite);
+        raise(&grid, &mut columns, peak, config.thermal.granite.angle - 5.);
+        let after = slump(&config, &columns, 10);
+        assert!(after.reflect_partial_eq(&columns) == Some(true), "a slope under the angle of repose moved");
+    }
+
+    #[test]
+    fn each_rock_type_stands_at_its_own_angle() {
+        for rock_type in [RockType::Basalt, RockType::Granite] {
+            let (mut config, grid, mut columns, peak) = flat_world(rock_type);
+            config.thermal.basalt.angle = 30.;
+            config.thermal.granite.angle = 60.;
+            raise(&grid, &mut columns, peak, 45.);
+            let after = slump(&config, &columns, 1);
+            let slumped = after.bedrock[peak] < columns.bedrock[peak];
+            assert_eq!(slumped, rock_type == RockType::Basalt, "{:?} at 45 degrees", rock_type);
+        }
+    }
+
+    #[test]
+    fn slumping_conserves_rock_and_sediment() {
+        for kind in [GridKind::LatLong, GridKind::Icosahedral, GridKind::CubeSphere] {
+            let config =
+                WorldConfig { grid: kind, rows: 30, cols: 40, ico_subdivisions: 3, cube_face_cells: 12, ..default() };
+            let grid = Grid::from_config(&config);
+
+            let mut columns = Columns::new(grid.cell_count(), RockType::Basalt, &config);
+            let mut rng = ChaCha8Rng::seed_from_u64(2);
+            for cell in 0..grid.cell_count() {
+                if rng.gen_bool(0.5) {
+                    columns.new_crust(cell, RockType::Granite, &config);
+                }
+                let rise = rng.gen_range(0.0..0.2);
+                columns.bedrock[cell] += rise;
+                columns.crust_thickness[cell] += rise;
+                columns.sediment[cell] = rng.gen_range(0.0..0.01);
+            }
+            let volume = |columns: &Columns| -> f64 {
+                (0..grid.cell_count())
+                    .map(|cell| (columns.bedrock[cell] + columns.sediment[cell]) as f64 * grid.cell_area(cell) as f64)
+                    .sum()
+            };
+
+            let after = slump(&config, &columns, 20);
+            let (before, after_volume) = (volume(&columns), volume(&after));
+            assert!(((after_volume - before) / before).abs() < 1e-6, "{:?}: {} became {}", kind, before, after_volume);
+
+            //and the ground did change, so there was something to conserve
+            let moved = columns.heights().iter().zip(after.heights()).map(|(a, b)| (a - b).abs()).fold(0f32, f32::max);
+            assert!(moved > 1e-3, "{:?}: nothing slumped", kind);
+        }
+    }
+}
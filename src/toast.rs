@@ -0,0 +1,110 @@
+// On-screen notifications
+//
+// Any system can send a ShowToast event, which pops up a short message at the bottom of the window that removes
+// itself after a few seconds.
+
+use bevy::prelude::*;
+
+// how long a toast stays on screen, in seconds
+const TOAST_SECONDS: f32 = 4.0;
+
+#[derive(Event)]
+pub struct ShowToast {
+    pub message: String,
+    pub is_error: bool,
+}
+
+impl ShowToast {
+    pub fn info(message: impl Into<String>) -> Self {
+        Self { message: message.into(), is_error: false }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self { message: message.into(), is_error: true }
+    }
+}
+
+#[derive(Component)]
+pub struct Toast {
+    timer: Timer,
+}
+
+// spawns a text box for every ShowToast event, newer toasts replace older ones
+pub fn spawn_toasts(
+    mut commands: Commands,
+    mut events: EventReader<ShowToast>,
+    toast_query: Query<Entity, With<Toast>>,
+) {
+    for event in events.read() {
+        for entity in &toast_query {
+            commands.entity(entity).despawn_recursive();
+        }
+
+        commands.spawn
+        (
+            (
+                // Create a TextBundle that has a Text with a single section.
+                TextBundle::from_section
+                (
+                    // The string to be displayed.
+                    event.message.clone(),
+
+                    // Sets the properties of the text.
+                    TextStyle
+                    {
+                        // Set the font of the text to default
+                        font: default(),
+
+                        // Set the font size of the text
+                        font_size: 24.0,
+
+                        // Errors are shown in red so they stand out
+                        color: if event.is_error { Color::rgb(1.0, 0.4, 0.4) } else { Color::rgb(0.9, 0.9, 0.9) },
+                    },
+                )
+
+                // Set the style of the TextBundle itself.
+                .with_style
+                (
+                    Style
+                    {
+                        // Place the toast over everything else
+                        position_type: PositionType::Absolute,
+
+                        // Set the number of pixels below the text field
+                        bottom: Val::Px(20.0),
+
+                        // Horizontally align the text field
+                        justify_self: JustifySelf::Center,
+
+                        // Leave some room around the message
+                        padding: UiRect::all(Val::Px(10.0)),
+
+                        ..default()
+                    }
+                )
+
+                // Darken the area behind the message so it is readable over the globe
+                .with_background_color(Color::rgba(0.0, 0.0, 0.0, 0.7)),
+
+                Toast
+                {
+                    timer: Timer::from_seconds(TOAST_SECONDS, TimerMode::Once),
+                },
+            )
+        );
+    }
+}
+
+// removes toasts once their time is up
+pub fn expire_toasts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut toast_query: Query<(Entity, &mut Toast)>,
+) {
+    for (entity, mut toast) in &mut toast_query {
+        if toast.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}